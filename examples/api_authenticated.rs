@@ -0,0 +1,22 @@
+mod api_utils;
+mod oauth_utils;
+
+use chrono::Utc;
+use patreon::{AuthenticatedApi, Tokens};
+
+#[tokio::main]
+async fn main() {
+    let tokens = Tokens {
+        access_token: env!("ACCESS_TOKEN").to_string(),
+        refresh_token: env!("REFRESH_TOKEN").to_string(),
+        expires_at: Utc::now(),
+        ..Default::default()
+    };
+    let api = AuthenticatedApi::new(api_utils::api_client(), oauth_utils::oauth_client(), tokens)
+        .on_refresh(|tokens| println!("refreshed : {:?}", tokens));
+    println!(
+        "{:?}",
+        api.call(|api| async move { api.current_user().await })
+            .await
+    );
+}
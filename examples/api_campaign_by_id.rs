@@ -0,0 +1,21 @@
+mod api_utils;
+
+use patreon::CampaignInclude;
+
+#[tokio::main]
+async fn main() {
+    let api = api_utils::api_client();
+    println!(
+        "{:?}",
+        api.campaign_by_id(
+            env!("CAMPAIGN_ID").to_string(),
+            &[
+                CampaignInclude::Tiers,
+                CampaignInclude::Benefits,
+                CampaignInclude::Goals,
+                CampaignInclude::Creator,
+            ],
+        )
+        .await
+    );
+}
@@ -0,0 +1,12 @@
+mod api_utils;
+
+use futures::TryStreamExt;
+
+#[tokio::main]
+async fn main() {
+    let api = api_utils::api_client();
+    let mut members = Box::pin(api.campaign_members(env!("CAMPAIGN_ID").to_string()));
+    while let Some(member) = members.try_next().await.unwrap() {
+        println!("{:?}", member);
+    }
+}
@@ -0,0 +1,13 @@
+mod api_utils;
+
+use futures::TryStreamExt;
+
+#[tokio::main]
+async fn main() {
+    let api = api_utils::api_client();
+    println!("{:?}", api.current_user_campaigns().await);
+    let mut pledges = Box::pin(api.campaign_pledges(env!("CAMPAIGN_ID").to_string()));
+    while let Some(pledge) = pledges.try_next().await.unwrap() {
+        println!("{:?}", pledge);
+    }
+}
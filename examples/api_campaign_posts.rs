@@ -0,0 +1,13 @@
+mod api_utils;
+
+use futures::TryStreamExt;
+
+#[tokio::main]
+async fn main() {
+    let api = api_utils::api_client();
+    let mut posts = Box::pin(api.campaign_posts(env!("CAMPAIGN_ID").to_string()));
+    while let Some(post) = posts.try_next().await.unwrap() {
+        println!("{:?}", post);
+    }
+    println!("{:?}", api.post_by_id(env!("POST_ID").to_string()).await);
+}
@@ -0,0 +1,18 @@
+mod api_utils;
+
+use patreon::{CampaignField, Query};
+
+#[tokio::main]
+async fn main() {
+    let api = api_utils::api_client();
+    let query = Query::new().fields(&[CampaignField::CreationName, CampaignField::Url]);
+    println!("{:?}", api.get_raw("api/oauth2/v2/campaigns", &query).await);
+    println!(
+        "{:?}",
+        api.get_document::<serde_json::Value>(
+            format!("api/oauth2/v2/campaigns/{}", env!("CAMPAIGN_ID")).as_str(),
+            &query,
+        )
+        .await
+    );
+}
@@ -0,0 +1,24 @@
+mod api_utils;
+
+use patreon::{IdentityIncldue, Resource};
+
+#[tokio::main]
+async fn main() {
+    let api = api_utils::api_client();
+    let (user, included) = api
+        .identity_include(&[
+            IdentityIncldue::MembershipsCampaign,
+            IdentityIncldue::MembershipsCurrentlyEntitledTiers,
+        ])
+        .await
+        .unwrap();
+    println!("{:?}", user);
+    for resource in included {
+        match resource {
+            Resource::Member(member) => println!("member {:?}", member),
+            Resource::Campaign(campaign) => println!("campaign {:?}", campaign),
+            Resource::Tier(tier) => println!("tier {:?}", tier),
+            other => println!("other {:?}", other),
+        }
+    }
+}
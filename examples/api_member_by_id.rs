@@ -0,0 +1,22 @@
+mod api_utils;
+
+use patreon::MemberInclude;
+
+#[tokio::main]
+async fn main() {
+    let api = api_utils::api_client();
+    println!(
+        "{:?}",
+        api.member_by_id_include(
+            env!("MEMBER_ID").to_string(),
+            &[
+                MemberInclude::Address,
+                MemberInclude::CurrentlyEntitledTiers,
+                MemberInclude::User,
+                MemberInclude::Campaign,
+                MemberInclude::PledgeHistory,
+            ],
+        )
+        .await
+    );
+}
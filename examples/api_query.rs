@@ -0,0 +1,16 @@
+mod api_utils;
+
+use patreon::{CampaignField, MemberField, Query, TierField, UserField};
+
+#[tokio::main]
+async fn main() {
+    let api = api_utils::api_client();
+    let query = Query::new()
+        .fields(&[UserField::FullName, UserField::Email])
+        .fields(&[MemberField::PatronStatus, MemberField::FullName])
+        .fields(&[CampaignField::CreationName, CampaignField::Url])
+        .fields(&[TierField::Title, TierField::AmountCents])
+        .include("memberships.campaign")
+        .include("memberships.currently_entitled_tiers");
+    println!("{:?}", api.identity_with_query(&query).await);
+}
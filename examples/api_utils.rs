@@ -1,10 +1,13 @@
 use patreon::PatreonApi;
+use std::time::Duration;
 
 pub fn api_client() -> PatreonApi {
-    PatreonApi {
-        access_token: env!("ACCESS_TOKEN").to_string(),
-        ..Default::default()
-    }
+    PatreonApi::builder()
+        .access_token(env!("ACCESS_TOKEN"))
+        .user_agent("patreon-rs examples")
+        .timeout(Duration::from_secs(30))
+        .build()
+        .unwrap()
 }
 
 #[allow(dead_code)]
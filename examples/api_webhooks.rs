@@ -0,0 +1,18 @@
+mod api_utils;
+
+use patreon::WebhookTrigger;
+
+#[tokio::main]
+async fn main() {
+    let api = api_utils::api_client();
+    println!(
+        "{:?}",
+        api.create_webhook(
+            env!("CAMPAIGN_ID").to_string(),
+            env!("WEBHOOK_URI").to_string(),
+            &[WebhookTrigger::MembersCreate, WebhookTrigger::MembersUpdate],
+        )
+        .await
+    );
+    println!("{:?}", api.webhooks().await);
+}
@@ -1,12 +1,15 @@
 mod oauth_utils;
 
+use patreon::{Scope, ScopeSet};
+
 fn main() {
     let oauth = oauth_utils::oauth_client();
-    println!(
-        "{}",
-        oauth.get_authorization_url(
-            "identity identity[email] identity.memberships campaigns campaigns.members",
-            "",
-        )
-    );
+    let scopes = ScopeSet::from([
+        Scope::Identity,
+        Scope::IdentityEmail,
+        Scope::IdentityMemberships,
+        Scope::Campaigns,
+        Scope::CampaignsMembers,
+    ]);
+    println!("{}", oauth.get_authorization_url(&scopes, ""));
 }
@@ -1,12 +1,14 @@
 use patreon::PatreonOAuth;
+use std::time::Duration;
 
 pub fn oauth_client() -> PatreonOAuth {
-    PatreonOAuth {
-        client_id: env!("CLIENT_ID").to_string(),
-        client_secret: env!("CLIENT_SECRET").to_string(),
-        redirect_uri: env!("REDIRECT_URI").to_string(),
-        ..Default::default()
-    }
+    PatreonOAuth::builder()
+        .client_id(env!("CLIENT_ID"))
+        .client_secret(env!("CLIENT_SECRET"))
+        .redirect_uri(env!("REDIRECT_URI"))
+        .timeout(Duration::from_secs(30))
+        .build()
+        .unwrap()
 }
 
 #[allow(dead_code)]
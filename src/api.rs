@@ -1,99 +1,676 @@
-use crate::{ApiError, PatreonError, PatreonResult};
+use crate::client::{
+    client_options_setters, default_retry_policy, required, ClientOptions, DEFAULT_USER_AGENT,
+};
+use crate::{
+    AddressField, ApiError, BenefitField, CampaignField, GoalField, MemberField, PatreonError,
+    PatreonResult, PledgeEventField, PostField, Query, RateLimiter, RetryPolicy, Scope, ScopeSet,
+    TierField, UserField, WebhookField,
+};
 use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream, TryStreamExt};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use url::Url;
 
-static BASE_URI: &str = "https://www.patreon.com";
+pub(crate) static BASE_URI: &str = "https://www.patreon.com";
 
-#[derive(Debug, Default)]
+/// Wait before retrying a 429 response that has no `Retry-After` header.
+static DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+static DEFAULT_MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+static USER_FIELDS: &[UserField] = &[
+    UserField::FirstName,
+    UserField::LastName,
+    UserField::FullName,
+    UserField::Vanity,
+    UserField::Email,
+    UserField::About,
+    UserField::ImageUrl,
+    UserField::ThumbUrl,
+    UserField::Created,
+    UserField::Url,
+];
+
+#[derive(Debug, Clone)]
 pub struct PatreonApi {
     pub access_token: String,
     pub agent: Arc<reqwest::Client>,
+    /// Root every api path is appended to, defaults to `https://www.patreon.com`.
+    pub base_url: Url,
+    pub user_agent: String,
+    /// How many times a rate limited (429) request is retried after sleeping `Retry-After`, 0 disables retries.
+    pub rate_limit_retries: u32,
+    /// Longest `Retry-After` worth sleeping on, a longer one fails with `RateLimited` right away.
+    pub max_retry_after: Duration,
+    /// Retries of failed GETs, rate limits are handled by `rate_limit_retries` instead.
+    pub retry_policy: Arc<dyn RetryPolicy>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Scopes the access token was granted, when known calls needing other scopes fail with `MissingScope` without a request.
+    pub granted_scopes: Option<ScopeSet>,
+}
+
+impl Default for PatreonApi {
+    fn default() -> Self {
+        Self {
+            access_token: String::default(),
+            agent: Arc::default(),
+            base_url: Url::parse(BASE_URI).unwrap(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            rate_limit_retries: 0,
+            max_retry_after: DEFAULT_MAX_RETRY_AFTER,
+            retry_policy: default_retry_policy(),
+            rate_limiter: None,
+            granted_scopes: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PatreonApiBuilder {
+    access_token: Option<String>,
+    rate_limit_retries: u32,
+    max_retry_after: Option<Duration>,
+    granted_scopes: Option<ScopeSet>,
+    options: ClientOptions,
+}
+
+impl PatreonApiBuilder {
+    pub fn access_token(mut self, access_token: impl Into<String>) -> Self {
+        self.access_token = Some(access_token.into());
+        self
+    }
+
+    client_options_setters!();
+
+    /// Sleep `Retry-After` and retry up to `retries` times when rate limited, instead of failing with `RateLimited`.
+    pub fn rate_limit_retries(mut self, retries: u32) -> Self {
+        self.rate_limit_retries = retries;
+        self
+    }
+
+    /// Defaults to 60 seconds.
+    pub fn max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = Some(max_retry_after);
+        self
+    }
+
+    /// Scopes granted to the access token (e.g. `Tokens::scope`), checked before every request.
+    pub fn granted_scopes(mut self, granted_scopes: ScopeSet) -> Self {
+        self.granted_scopes = Some(granted_scopes);
+        self
+    }
+
+    /// Fails when `access_token` is missing or `base_url` / `user_agent` are invalid.
+    pub fn build(self) -> PatreonResult<PatreonApi> {
+        Ok(PatreonApi {
+            access_token: required("access_token", self.access_token)?,
+            base_url: self.options.base_url(BASE_URI)?,
+            user_agent: self.options.user_agent()?,
+            retry_policy: self.options.retry_policy(),
+            rate_limiter: self.options.rate_limiter.clone(),
+            agent: Arc::new(self.options.agent()?),
+            rate_limit_retries: self.rate_limit_retries,
+            max_retry_after: self.max_retry_after.unwrap_or(DEFAULT_MAX_RETRY_AFTER),
+            granted_scopes: self.granted_scopes,
+        })
+    }
 }
 
 impl PatreonApi {
+    pub fn builder() -> PatreonApiBuilder {
+        PatreonApiBuilder::default()
+    }
+
     pub async fn current_user(&self) -> PatreonResult<User> {
-        let mut url = Url::parse(BASE_URI).unwrap();
-        url.set_path("/api/oauth2/api/current_user");
-        let request = self.agent.get(url);
-        self.call_data(request).await
+        Ok(self.current_user_with_query(&Query::new()).await?.data)
+    }
+
+    pub async fn current_user_with_query(&self, query: &Query) -> PatreonResult<Document<User>> {
+        let url = self.api_url("/api/oauth2/api/current_user", query);
+        self.call_document(self.agent.get(url), &requires(&[Scope::Users]))
+            .await
+    }
+
+    pub async fn current_user_campaigns(&self) -> PatreonResult<Vec<CurrentUserCampaign>> {
+        let document = self
+            .current_user_campaigns_with_query(&Query::new())
+            .await?;
+        document
+            .data
+            .iter()
+            .map(|campaign| {
+                Ok(CurrentUserCampaign {
+                    creator: document.related_as(campaign, "creator")?.pop(),
+                    rewards: document.related_as(campaign, "rewards")?,
+                    campaign: campaign.clone(),
+                })
+            })
+            .collect()
+    }
+
+    pub async fn current_user_campaigns_with_query(
+        &self,
+        query: &Query,
+    ) -> PatreonResult<Document<Vec<Campaign>>> {
+        let url = self.api_url("/api/oauth2/api/current_user/campaigns", query);
+        self.call_document(self.agent.get(url), &requires(&[Scope::MyCampaign]))
+            .await
+    }
+
+    /// Pledges of a campaign with their patron and reward, pages are fetched while the stream is polled.
+    pub fn campaign_pledges(
+        &self,
+        campaign_id: String,
+    ) -> impl Stream<Item = PatreonResult<CampaignPledge>> + '_ {
+        let query = Query::new().include("patron").include("reward");
+        self.campaign_pledges_with_query(campaign_id, &query)
+            .map_ok(|page| {
+                let pledges = page
+                    .data
+                    .iter()
+                    .map(|pledge| {
+                        Ok(CampaignPledge {
+                            patron: page.related_as(pledge, "patron")?.pop(),
+                            reward: page.related_as(pledge, "reward")?.pop(),
+                            pledge: pledge.clone(),
+                        })
+                    })
+                    .collect::<Vec<PatreonResult<CampaignPledge>>>();
+                stream::iter(pledges)
+            })
+            .try_flatten()
+    }
+
+    pub fn campaign_pledges_with_query(
+        &self,
+        campaign_id: String,
+        query: &Query,
+    ) -> impl Stream<Item = PatreonResult<Document<Vec<Pledge>>>> + '_ {
+        let url = self.api_url(
+            format!("/api/oauth2/api/campaigns/{}/pledges", campaign_id).as_str(),
+            query,
+        );
+        self.paginate_pages(url, requires(&[Scope::PledgesToMe]))
     }
 
     pub async fn member_by_id(&self, member_id: String) -> PatreonResult<CampaignMember> {
-        let request = self.member_by_id_request(member_id);
-        self.call_data(request).await
+        let query = Query::new().fields(&[MemberField::PatronStatus]);
+        let url = self.api_url(
+            format!("api/oauth2/v2/members/{}", member_id).as_str(),
+            &query,
+        );
+        self.call_data(self.agent.get(url), &member_scopes(&query))
+            .await
+    }
+
+    pub async fn member_by_id_with_query(
+        &self,
+        member_id: String,
+        query: &Query,
+    ) -> PatreonResult<Document<Member>> {
+        let url = self.api_url(
+            format!("api/oauth2/v2/members/{}", member_id).as_str(),
+            query,
+        );
+        self.call_document(self.agent.get(url), &member_scopes(query))
+            .await
+    }
+
+    pub async fn member_by_id_include(
+        &self,
+        member_id: String,
+        includes: &[MemberInclude],
+    ) -> PatreonResult<MemberDetail> {
+        let mut query = Query::new().fields(MemberField::ALL);
+        for include in includes {
+            query = query.include(include.as_str());
+            query = match include {
+                MemberInclude::Address => query.fields(AddressField::ALL),
+                MemberInclude::CurrentlyEntitledTiers => query.fields(TierField::ALL),
+                MemberInclude::User => query.fields(USER_FIELDS),
+                MemberInclude::Campaign => query.fields(CampaignField::ALL),
+                MemberInclude::PledgeHistory => query.fields(PledgeEventField::ALL),
+                MemberInclude::Unknown(_) => query,
+            };
+        }
+        let document = self.member_by_id_with_query(member_id, &query).await?;
+        let mut detail = MemberDetail {
+            member: document.data,
+            ..Default::default()
+        };
+        for resource in document.included {
+            match resource {
+                Resource::Address(address) => detail.address = Some(address),
+                Resource::Tier(tier) => detail.currently_entitled_tiers.push(tier),
+                Resource::User(user) => detail.user = Some(user),
+                Resource::Campaign(campaign) => detail.campaign = Some(campaign),
+                Resource::PledgeEvent(event) => detail.pledge_history.push(event),
+                _ => {}
+            }
+        }
+        Ok(detail)
+    }
+
+    /// Pledge events of a member, oldest first.
+    pub async fn member_pledge_history(
+        &self,
+        member_id: String,
+    ) -> PatreonResult<Vec<PledgeEvent>> {
+        let mut history = self
+            .member_by_id_include(member_id, &[MemberInclude::PledgeHistory])
+            .await?
+            .pledge_history;
+        history.sort_by_key(|event| event.attributes.date);
+        Ok(history)
     }
 
     pub async fn identity(&self) -> PatreonResult<User> {
-        self.call_data(self.identity_request(None)).await
+        Ok(self.identity_with_query(&identity_query(&[])).await?.data)
     }
 
-    pub async fn identity_include_memberships(&self) -> PatreonResult<(User, Vec<Member>)> {
-        self.call_data_and_include(self.identity_request(IdentityIncldue::Memberships))
+    pub async fn identity_with_query(&self, query: &Query) -> PatreonResult<Document<User>> {
+        let url = self.api_url("api/oauth2/v2/identity", query);
+        self.call_document(self.agent.get(url), &identity_scopes(query))
             .await
     }
 
+    pub async fn identity_include_memberships(&self) -> PatreonResult<(User, Vec<Member>)> {
+        let document = self
+            .identity_with_query(&identity_query(&[IdentityIncldue::Memberships]))
+            .await?;
+        let memberships = document.related_as(&document.data, "memberships")?;
+        Ok((document.data, memberships))
+    }
+
+    /// Memberships of the token owner joined to the campaign they belong to.
+    pub async fn identity_include_memberships_campaign(
+        &self,
+    ) -> PatreonResult<(User, Vec<(Member, Option<Campaign>)>)> {
+        let document = self
+            .identity_with_query(&identity_query(&[IdentityIncldue::MembershipsCampaign]))
+            .await?;
+        let memberships = document
+            .related_as::<Member, _>(&document.data, "memberships")?
+            .into_iter()
+            .map(|member| {
+                let campaign = document.related_as(&member, "campaign")?.pop();
+                Ok((member, campaign))
+            })
+            .collect::<PatreonResult<Vec<_>>>()?;
+        Ok((document.data, memberships))
+    }
+
     pub async fn identity_include_campaign(&self) -> PatreonResult<(User, Vec<Campaign>)> {
-        self.call_data_and_include(self.identity_request(IdentityIncldue::Campaign))
+        let document = self
+            .identity_with_query(&identity_query(&[IdentityIncldue::Campaign]))
+            .await?;
+        let campaign = document.related_as(&document.data, "campaign")?;
+        Ok((document.data, campaign))
+    }
+
+    /// The token owner with every included resource, whatever its type.
+    pub async fn identity_include(
+        &self,
+        includes: &[IdentityIncldue],
+    ) -> PatreonResult<(User, Vec<Resource>)> {
+        let document = self.identity_with_query(&identity_query(includes)).await?;
+        Ok((document.data, document.included))
+    }
+
+    /// All campaigns the token owner can see, every page is fetched.
+    pub async fn campaigns(&self) -> PatreonResult<Vec<Campaign>> {
+        self.campaigns_with_query(&Query::new().fields(CampaignField::ALL))
+            .map_ok(|page| stream::iter(page.data.into_iter().map(PatreonResult::Ok)))
+            .try_flatten()
+            .try_collect()
             .await
     }
 
-    fn identity_request(
+    pub fn campaigns_with_query(
+        &self,
+        query: &Query,
+    ) -> impl Stream<Item = PatreonResult<Document<Vec<Campaign>>>> + '_ {
+        self.paginate_pages(
+            self.api_url("api/oauth2/v2/campaigns", query),
+            requires(&[Scope::Campaigns]),
+        )
+    }
+
+    pub fn campaign_members(
+        &self,
+        campaign_id: String,
+    ) -> impl Stream<Item = PatreonResult<Member>> + '_ {
+        self.campaign_members_with_query(campaign_id, &Query::new().fields(MemberField::ALL))
+            .map_ok(|page| stream::iter(page.data.into_iter().map(PatreonResult::Ok)))
+            .try_flatten()
+    }
+
+    pub fn campaign_members_with_query(
         &self,
-        include: impl Into<Option<IdentityIncldue>>,
-    ) -> reqwest::RequestBuilder {
-        let mut url = Url::parse(BASE_URI).unwrap();
-        url.set_path("api/oauth2/v2/identity");
-        url.query_pairs_mut().append_pair(
-            "fields[user]",
-            "first_name,last_name,full_name,vanity,email,about,image_url,thumb_url,created,url",
+        campaign_id: String,
+        query: &Query,
+    ) -> impl Stream<Item = PatreonResult<Document<Vec<Member>>>> + '_ {
+        let url = self.api_url(
+            format!("api/oauth2/v2/campaigns/{}/members", campaign_id).as_str(),
+            query,
         );
-        let include = include.into();
-        if let Some(include) = include {
-            url.query_pairs_mut()
-                .append_pair("include", include.as_str());
-            match include {
-                IdentityIncldue::Memberships => {
-                    url.query_pairs_mut().append_pair(
-                        "fields[member]",
-                        "campaign_lifetime_support_cents,currently_entitled_amount_cents,email,full_name,is_follower,last_charge_date,last_charge_status,lifetime_support_cents,next_charge_date,note,patron_status,pledge_cadence,pledge_relationship_start,will_pay_amount_cents",
-                    );
-                }
-                IdentityIncldue::Campaign => {
-                    url.query_pairs_mut()
-                        .append_pair("fields[campaign]", "created_at,creation_name,discord_server_id,google_analytics_id,has_rss,has_sent_rss_notify,image_small_url,image_url,is_charged_immediately,is_monthly,is_nsfw,main_video_embed,main_video_url,one_liner,patron_count,pay_per_name,pledge_url,published_at,rss_artwork_url,rss_feed_title,show_earnings,summary,thanks_embed,thanks_msg,thanks_video_url,url,vanity");
-                }
+        self.paginate_pages(url, member_scopes(query))
+    }
+
+    pub fn campaign_posts(
+        &self,
+        campaign_id: String,
+    ) -> impl Stream<Item = PatreonResult<Post>> + '_ {
+        self.campaign_posts_with_query(campaign_id, &Query::new().fields(PostField::ALL))
+            .map_ok(|page| stream::iter(page.data.into_iter().map(PatreonResult::Ok)))
+            .try_flatten()
+    }
+
+    pub fn campaign_posts_with_query(
+        &self,
+        campaign_id: String,
+        query: &Query,
+    ) -> impl Stream<Item = PatreonResult<Document<Vec<Post>>>> + '_ {
+        let url = self.api_url(
+            format!("api/oauth2/v2/campaigns/{}/posts", campaign_id).as_str(),
+            query,
+        );
+        self.paginate_pages(url, requires(&[Scope::CampaignsPosts]))
+    }
+
+    pub async fn post_by_id(&self, post_id: String) -> PatreonResult<Post> {
+        Ok(self
+            .post_by_id_with_query(post_id, &Query::new().fields(PostField::ALL))
+            .await?
+            .data)
+    }
+
+    pub async fn post_by_id_with_query(
+        &self,
+        post_id: String,
+        query: &Query,
+    ) -> PatreonResult<Document<Post>> {
+        let url = self.api_url(format!("api/oauth2/v2/posts/{}", post_id).as_str(), query);
+        self.call_document(self.agent.get(url), &requires(&[Scope::CampaignsPosts]))
+            .await
+    }
+
+    pub async fn webhooks(&self) -> PatreonResult<Vec<WebhookResource>> {
+        self.webhooks_with_query(&Query::new().fields(WebhookField::ALL))
+            .map_ok(|page| stream::iter(page.data.into_iter().map(PatreonResult::Ok)))
+            .try_flatten()
+            .try_collect()
+            .await
+    }
+
+    pub fn webhooks_with_query(
+        &self,
+        query: &Query,
+    ) -> impl Stream<Item = PatreonResult<Document<Vec<WebhookResource>>>> + '_ {
+        self.paginate_pages(
+            self.api_url("api/oauth2/v2/webhooks", query),
+            requires(&[Scope::CampaignsWebhook]),
+        )
+    }
+
+    pub async fn create_webhook(
+        &self,
+        campaign_id: String,
+        uri: String,
+        triggers: &[WebhookTrigger],
+    ) -> PatreonResult<WebhookResource> {
+        let query = Query::new().fields(WebhookField::ALL);
+        Ok(self
+            .create_webhook_with_query(campaign_id, uri, triggers, &query)
+            .await?
+            .data)
+    }
+
+    pub async fn create_webhook_with_query(
+        &self,
+        campaign_id: String,
+        uri: String,
+        triggers: &[WebhookTrigger],
+        query: &Query,
+    ) -> PatreonResult<Document<WebhookResource>> {
+        let body = serde_json::json!({
+            "data": {
+                "type": "webhook",
+                "attributes": {
+                    "triggers": triggers,
+                    "uri": uri,
+                },
+                "relationships": {
+                    "campaign": {
+                        "data": { "type": "campaign", "id": campaign_id },
+                    },
+                },
+            },
+        });
+        let url = self.api_url("api/oauth2/v2/webhooks", query);
+        let request = self
+            .agent
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&body)?);
+        self.call_document(request, &requires(&[Scope::CampaignsWebhook]))
+            .await
+    }
+
+    pub async fn update_webhook(
+        &self,
+        webhook_id: String,
+        update: &WebhookUpdate,
+    ) -> PatreonResult<WebhookResource> {
+        let query = Query::new().fields(WebhookField::ALL);
+        Ok(self
+            .update_webhook_with_query(webhook_id, update, &query)
+            .await?
+            .data)
+    }
+
+    pub async fn update_webhook_with_query(
+        &self,
+        webhook_id: String,
+        update: &WebhookUpdate,
+        query: &Query,
+    ) -> PatreonResult<Document<WebhookResource>> {
+        let url = self.api_url(
+            format!("api/oauth2/v2/webhooks/{}", webhook_id).as_str(),
+            query,
+        );
+        let body = serde_json::json!({
+            "data": {
+                "type": "webhook",
+                "id": webhook_id,
+                "attributes": update,
+            },
+        });
+        let request = self
+            .agent
+            .patch(url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&body)?);
+        self.call_document(request, &requires(&[Scope::CampaignsWebhook]))
+            .await
+    }
+
+    pub async fn delete_webhook(&self, webhook_id: String) -> PatreonResult<()> {
+        let url = self.api_url(
+            format!("api/oauth2/v2/webhooks/{}", webhook_id).as_str(),
+            &Query::new(),
+        );
+        self.api_call(
+            self.agent.delete(url),
+            &requires(&[Scope::CampaignsWebhook]),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn campaign_by_id(
+        &self,
+        campaign_id: String,
+        includes: &[CampaignInclude],
+    ) -> PatreonResult<CampaignDetail> {
+        let mut query = Query::new().fields(CampaignField::ALL);
+        for include in includes {
+            query = query.include(include.as_str());
+            query = match include {
+                CampaignInclude::Tiers => query.fields(TierField::ALL),
+                CampaignInclude::Benefits => query.fields(BenefitField::ALL),
+                CampaignInclude::Goals => query.fields(GoalField::ALL),
+                CampaignInclude::Creator => query.fields(USER_FIELDS),
+                CampaignInclude::Unknown(_) => query,
+            };
+        }
+        let document = self.campaign_by_id_with_query(campaign_id, &query).await?;
+        let mut detail = CampaignDetail {
+            campaign: document.data,
+            ..Default::default()
+        };
+        for resource in document.included {
+            match resource {
+                Resource::Tier(tier) => detail.tiers.push(tier),
+                Resource::Benefit(benefit) => detail.benefits.push(benefit),
+                Resource::Goal(goal) => detail.goals.push(goal),
+                Resource::User(user) => detail.creator = Some(user),
+                _ => {}
             }
         }
-        self.agent.get(url)
+        Ok(detail)
     }
 
-    fn member_by_id_request(
+    pub async fn campaign_by_id_with_query(
         &self,
-        member_id: String,
-    ) -> reqwest::RequestBuilder {
-        let mut url = Url::parse(BASE_URI).unwrap();
-        url.set_path(format!("api/oauth2/v2/members/{}", member_id).as_str());
-        url.query_pairs_mut().append_pair(
-            "fields[member]",
-            "patron_status",
+        campaign_id: String,
+        query: &Query,
+    ) -> PatreonResult<Document<Campaign>> {
+        let url = self.api_url(
+            format!("api/oauth2/v2/campaigns/{}", campaign_id).as_str(),
+            query,
         );
-        self.agent.get(url)
+        self.call_document(self.agent.get(url), &requires(&[Scope::Campaigns]))
+            .await
     }
 
-    async fn api_call(&self, request: reqwest::RequestBuilder) -> PatreonResult<String> {
-        let request = request
+    /// GET any api path (e.g. `api/oauth2/v2/campaigns`) without modelling it, `data` and `included` stay untyped.
+    pub async fn get_raw(
+        &self,
+        path: &str,
+        query: &Query,
+    ) -> PatreonResult<Document<serde_json::Value, serde_json::Value>> {
+        let url = self.api_url(path, query);
+        self.call_document(self.agent.get(url), &ScopeSet::new())
+            .await
+    }
+
+    /// GET any api path whose `data` is a single resource with attributes `A`.
+    pub async fn get_document<A: for<'de> serde::Deserialize<'de> + Default>(
+        &self,
+        path: &str,
+        query: &Query,
+    ) -> PatreonResult<Document<ApiDocument<A>>> {
+        let url = self.api_url(path, query);
+        self.call_document(self.agent.get(url), &ScopeSet::new())
+            .await
+    }
+
+    fn api_url(&self, path: &str, query: &Query) -> Url {
+        let mut url = join_path(&self.base_url, path);
+        query.append_to(&mut url);
+        url
+    }
+
+    /// Sends `request` unless `required` is known not to be granted (v1 scopes are left to the api),
+    /// a 403 is reported as `MissingScope`.
+    async fn api_call(
+        &self,
+        request: reqwest::RequestBuilder,
+        required: &ScopeSet,
+    ) -> PatreonResult<String> {
+        if let Some(granted) = &self.granted_scopes {
+            let mut checked = required.iter().filter(|scope| !scope.is_v1());
+            if !checked.all(|scope| granted.contains(scope)) {
+                return Err(self.missing_scope(required, vec![]));
+            }
+        }
+        let mut request = request
             .header("Authorization", format!("Bearer {}", self.access_token))
-            .header("User-Agent", "Patreon-rust")
+            .header("User-Agent", self.user_agent.as_str())
             .build()?;
+        let idempotent = request.method() == reqwest::Method::GET;
+        let mut rate_limited = 0;
+        let mut attempt = 0;
+        loop {
+            let retry = request.try_clone();
+            let result = self.execute(request).await;
+            let wait = match &result {
+                Err(PatreonError::RateLimited(retry_after))
+                    if rate_limited < self.rate_limit_retries
+                        && retry_after.unwrap_or(DEFAULT_RETRY_AFTER) <= self.max_retry_after =>
+                {
+                    rate_limited += 1;
+                    Some(retry_after.unwrap_or(DEFAULT_RETRY_AFTER))
+                }
+                Err(PatreonError::RateLimited(_)) => None,
+                Err(err) if idempotent => {
+                    attempt += 1;
+                    self.retry_policy.retry_after(attempt, err)
+                }
+                _ => None,
+            };
+            match (wait, retry) {
+                (Some(wait), Some(retry)) => {
+                    tracing::debug!("RETRY : {:?} in {:?}", result.err(), wait);
+                    tokio::time::sleep(wait).await;
+                    request = retry;
+                }
+                _ => {
+                    return result.map_err(|err| match err {
+                        PatreonError::PatreonApi(reqwest::StatusCode::FORBIDDEN, errors)
+                            if !required.is_empty() =>
+                        {
+                            self.missing_scope(required, errors)
+                        }
+                        err => err,
+                    })
+                }
+            }
+        }
+    }
+
+    fn missing_scope(&self, required: &ScopeSet, errors: Vec<ApiError>) -> PatreonError {
+        PatreonError::MissingScope {
+            required: required.clone(),
+            granted: self.granted_scopes.clone(),
+            errors,
+        }
+    }
+
+    async fn execute(&self, request: reqwest::Request) -> PatreonResult<String> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
         tracing::debug!("REQUEST : {} : {}", request.method(), request.url());
         let response = self.agent.execute(request).await?;
         let status = response.status();
+        let retry_after = retry_after(response.headers());
         let text = response.text().await?;
         tracing::debug!("RESPONSE : {status} : {text}");
         if status.is_success() {
             Ok(text)
+        } else if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            Err(PatreonError::RateLimited(retry_after))
+        } else if status.is_server_error() || status == reqwest::StatusCode::UNAUTHORIZED {
+            // gateways answer 5xx with html, the status alone is enough to retry or refresh the token
+            let errors = serde_json::from_str::<ApiErrorResponse>(text.as_str())
+                .map(|response| response.errors)
+                .unwrap_or_default();
+            Err(PatreonError::PatreonApi(status, errors))
         } else {
             Err(PatreonError::PatreonApi(
                 status,
@@ -105,21 +682,48 @@ impl PatreonApi {
     async fn call_data<T: for<'de> serde::Deserialize<'de>>(
         &self,
         request: reqwest::RequestBuilder,
+        required: &ScopeSet,
     ) -> PatreonResult<T> {
-        let json = self.api_call(request).await?;
+        let json = self.api_call(request, required).await?;
         DocResponse::parse(json.as_str())
     }
 
-    async fn call_data_and_include<
-        D: for<'de> serde::Deserialize<'de>,
-        I: for<'de> serde::Deserialize<'de> + Default,
-    >(
+    fn paginate_pages<'a, T: for<'de> serde::Deserialize<'de> + 'a>(
+        &'a self,
+        url: Url,
+        required: ScopeSet,
+    ) -> impl Stream<Item = PatreonResult<Document<Vec<T>>>> + 'a {
+        stream::try_unfold(Some(url.clone()), move |next| {
+            self.call_page::<T>(next, url.clone(), required.clone())
+        })
+    }
+
+    async fn call_page<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        next: Option<Url>,
+        base: Url,
+        required: ScopeSet,
+    ) -> PatreonResult<Option<(Document<Vec<T>>, Option<Url>)>> {
+        let Some(url) = next else {
+            return Ok(None);
+        };
+        let json = self.api_call(self.agent.get(url), &required).await?;
+        let page = serde_json::from_str::<Document<Vec<T>>>(json.as_str())?;
+        let next = page.next_url(&base);
+        Ok(Some((page, next)))
+    }
+
+    async fn call_document<D, I>(
         &self,
         request: reqwest::RequestBuilder,
-    ) -> PatreonResult<(D, Vec<I>)> {
-        let json = self.api_call(request).await?;
-        let response = serde_json::from_str::<DocResponseInclude<D, I>>(json.as_str())?;
-        Ok((response.data, response.included))
+        required: &ScopeSet,
+    ) -> PatreonResult<Document<D, I>>
+    where
+        D: for<'de> serde::Deserialize<'de>,
+        I: for<'de> serde::Deserialize<'de>,
+    {
+        let json = self.api_call(request, required).await?;
+        Ok(serde_json::from_str(json.as_str())?)
     }
 }
 
@@ -137,12 +741,319 @@ where
     }
 }
 
+/// `Retry-After` is either a number of seconds or an http date.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+/// Appends `path` to the path of `base`, so a base url with a prefix (e.g. a gateway) keeps it.
+pub(crate) fn join_path(base: &Url, path: &str) -> Url {
+    let mut url = base.clone();
+    url.set_path(
+        format!(
+            "{}/{}",
+            base.path().trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+        .as_str(),
+    );
+    url
+}
+
+fn requires(scopes: &[Scope]) -> ScopeSet {
+    ScopeSet::from(scopes)
+}
+
+// without these scopes patreon answers with an empty `included` instead of an error
+fn identity_scopes(query: &Query) -> ScopeSet {
+    let mut scopes = requires(&[Scope::Identity]);
+    if query.has_include("memberships") {
+        scopes.insert(Scope::IdentityMemberships);
+    }
+    if query.has_include("campaign") {
+        scopes.insert(Scope::Campaigns);
+    }
+    scopes
+}
+
+fn member_scopes(query: &Query) -> ScopeSet {
+    let mut scopes = requires(&[Scope::CampaignsMembers]);
+    if query.has_include("address") {
+        scopes.insert(Scope::CampaignsMembersAddress);
+    }
+    scopes
+}
+
+fn identity_query(includes: &[IdentityIncldue]) -> Query {
+    let mut query = Query::new().fields(USER_FIELDS);
+    for include in includes {
+        query = query.include(include.as_str());
+        query = match include {
+            IdentityIncldue::Memberships => query.fields(MemberField::ALL),
+            IdentityIncldue::Campaign => query.fields(CampaignField::ALL),
+            IdentityIncldue::MembershipsCampaign => {
+                query.fields(MemberField::ALL).fields(CampaignField::ALL)
+            }
+            IdentityIncldue::MembershipsCurrentlyEntitledTiers => {
+                query.fields(MemberField::ALL).fields(TierField::ALL)
+            }
+            IdentityIncldue::Unknown(_) => query,
+        };
+    }
+    query
+}
+
+/// A JSON:API top level document, `included` resources are parsed by their `type` unless `I` says otherwise.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub(crate) struct DocResponseInclude<D, I> {
-    data: D,
+pub struct Document<D, I = Resource> {
+    pub data: D,
+    #[serde(default = "Vec::new")]
+    pub included: Vec<I>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<Links>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<serde_json::Value>,
+}
+
+impl<D, I: Identify> Document<D, I> {
+    pub fn find(&self, identifier: &impl Identify) -> Option<&I> {
+        self.included.iter().find(|resource| {
+            resource.resource_type() == identifier.resource_type()
+                && resource.resource_id() == identifier.resource_id()
+        })
+    }
+
+    /// Included resources the relationship points at, identifiers without an included resource are skipped.
+    pub fn resolve(&self, relationship: &Relationship) -> Vec<&I> {
+        relationship
+            .identifiers()
+            .iter()
+            .filter_map(|identifier| self.find(*identifier))
+            .collect()
+    }
+
+    /// Included resources related to `resource` through the relationship called `name`.
+    pub fn related<A>(&self, resource: &ApiDocument<A>, name: &str) -> Vec<&I> {
+        resource
+            .relationships
+            .get(name)
+            .map(|relationship| self.resolve(relationship))
+            .unwrap_or_default()
+    }
+}
+
+impl<D> Document<D, Resource> {
+    /// Like `related`, every resolved resource must be a `T`.
+    pub fn related_as<T: TryFrom<Resource, Error = PatreonError>, A>(
+        &self,
+        resource: &ApiDocument<A>,
+        name: &str,
+    ) -> PatreonResult<Vec<T>> {
+        self.related(resource, name)
+            .into_iter()
+            .map(|resource| T::try_from(resource.clone()))
+            .collect()
+    }
+}
+
+impl<D, I> Document<D, I> {
+    /// Url of the next page on `base`, only the query of `links.next` is used so the
+    /// token never goes to another origin, otherwise the cursor is appended to `base`.
+    pub(crate) fn next_url(&self, base: &Url) -> Option<Url> {
+        if let Some(next) = self.links.as_ref().and_then(|links| links.next.as_ref()) {
+            if let Ok(next) = base.join(next) {
+                let mut url = base.clone();
+                url.set_query(next.query());
+                return Some(url);
+            }
+        }
+        let cursor = self
+            .meta
+            .as_ref()?
+            .pointer("/pagination/cursors/next")?
+            .as_str()?;
+        let mut url = base.clone();
+        url.query_pairs_mut().append_pair("page[cursor]", cursor);
+        Some(url)
+    }
+}
+
+/// Anything that carries a JSON:API `(type, id)` pair.
+pub trait Identify {
+    fn resource_type(&self) -> &str;
+    fn resource_id(&self) -> &str;
+}
+
+impl<A> Identify for ApiDocument<A> {
+    fn resource_type(&self) -> &str {
+        self.document_type.as_str()
+    }
+
+    fn resource_id(&self) -> &str {
+        self.id.as_str()
+    }
+}
+
+impl Identify for ResourceIdentifier {
+    fn resource_type(&self) -> &str {
+        self.resource_type.as_str()
+    }
+
+    fn resource_id(&self) -> &str {
+        self.id.as_str()
+    }
+}
+
+impl Identify for serde_json::Value {
+    fn resource_type(&self) -> &str {
+        self.get("type")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+    }
+
+    fn resource_id(&self) -> &str {
+        self.get("id")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+    }
+}
+
+macro_rules! resource_enum {
+    ($($variant:ident($ty:ty, $type_str:expr), )*) => {
+        /// An included resource dispatched on its JSON:API `type`, unknown types are kept as `Raw`.
+        #[derive(Debug, Clone, PartialEq, Serialize)]
+        #[serde(untagged)]
+        pub enum Resource {
+            $($variant($ty),)*
+            Raw(serde_json::Value),
+        }
+
+        impl Resource {
+            fn identity(&self) -> &dyn Identify {
+                match self {
+                    $( Resource::$variant(resource) => resource, )*
+                    Resource::Raw(value) => value,
+                }
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for Resource {
+            fn deserialize<De>(deserializer: De) -> Result<Self, De::Error>
+            where
+                De: serde::Deserializer<'de>,
+            {
+                use serde::de::Error;
+                let value = serde_json::Value::deserialize(deserializer)?;
+                match value.resource_type() {
+                    $( $type_str => serde_json::from_value(value)
+                        .map(Resource::$variant)
+                        .map_err(De::Error::custom), )*
+                    _ => Ok(Resource::Raw(value)),
+                }
+            }
+        }
+
+        $(
+            impl From<$ty> for Resource {
+                fn from(value: $ty) -> Self {
+                    Resource::$variant(value)
+                }
+            }
+
+            impl TryFrom<Resource> for $ty {
+                type Error = PatreonError;
+
+                fn try_from(value: Resource) -> PatreonResult<Self> {
+                    match value {
+                        Resource::$variant(resource) => Ok(resource),
+                        other => Err(PatreonError::Message(format!(
+                            "expected {} resource, found {}",
+                            $type_str,
+                            other.resource_type(),
+                        ))),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+resource_enum!(
+    User(User, "user"),
+    Member(Member, "member"),
+    Campaign(Campaign, "campaign"),
+    Tier(Tier, "tier"),
+    Benefit(Benefit, "benefit"),
+    Goal(Goal, "goal"),
+    Address(Address, "address"),
+    Post(Post, "post"),
+    PledgeEvent(PledgeEvent, "pledge-event"),
+    Webhook(WebhookResource, "webhook"),
+    Reward(Reward, "reward"),
+    Pledge(Pledge, "pledge"),
+);
+
+impl Identify for Resource {
+    fn resource_type(&self) -> &str {
+        self.identity().resource_type()
+    }
+
+    fn resource_id(&self) -> &str {
+        self.identity().resource_id()
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ResourceIdentifier {
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    pub id: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relationship {
     #[serde(default)]
-    // if not default and identity?include=campaign and not has it access in scopes be "missing field `included`"
-    included: Vec<I>,
+    pub data: Option<RelationshipData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<Links>,
+}
+
+impl Relationship {
+    pub fn identifiers(&self) -> Vec<&ResourceIdentifier> {
+        match &self.data {
+            Some(RelationshipData::One(identifier)) => vec![identifier],
+            Some(RelationshipData::Many(identifiers)) => identifiers.iter().collect(),
+            None => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RelationshipData {
+    One(ResourceIdentifier),
+    Many(Vec<ResourceIdentifier>),
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Links {
+    #[serde(rename = "self", default, skip_serializing_if = "Option::is_none")]
+    pub self_link: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub related: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -150,72 +1061,135 @@ pub struct ApiDocument<A> {
     #[serde(rename = "type")]
     pub document_type: String,
     pub id: String,
+    #[serde(default)]
     pub attributes: A,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub relationships: HashMap<String, Relationship>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<Links>,
 }
 
 pub type User = ApiDocument<UserAttributes>;
 pub type Member = ApiDocument<MemberAttributes>;
 pub type Campaign = ApiDocument<CampaignAttributes>;
 pub type CampaignMember = ApiDocument<CampaignMemberAttributes>;
+pub type Tier = ApiDocument<TierAttributes>;
+pub type Benefit = ApiDocument<BenefitAttributes>;
+pub type Goal = ApiDocument<GoalAttributes>;
+pub type Post = ApiDocument<PostAttributes>;
+pub type Address = ApiDocument<AddressAttributes>;
+pub type Reward = ApiDocument<RewardAttributes>;
+pub type PledgeEvent = ApiDocument<PledgeEventAttributes>;
+pub type WebhookResource = ApiDocument<WebhookAttributes>;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CampaignDetail {
+    pub campaign: Campaign,
+    pub tiers: Vec<Tier>,
+    pub benefits: Vec<Benefit>,
+    pub goals: Vec<Goal>,
+    pub creator: Option<User>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurrentUserCampaign {
+    pub campaign: Campaign,
+    pub creator: Option<User>,
+    pub rewards: Vec<Reward>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CampaignPledge {
+    pub pledge: Pledge,
+    pub patron: Option<User>,
+    pub reward: Option<Reward>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemberDetail {
+    pub member: Member,
+    pub address: Option<Address>,
+    pub currently_entitled_tiers: Vec<Tier>,
+    pub user: Option<User>,
+    pub campaign: Option<Campaign>,
+    pub pledge_history: Vec<PledgeEvent>,
+}
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserAttributes {
-    pub first_name: String,
-    pub last_name: String,
-    pub full_name: String,
-    pub vanity: Option<String>,
     pub about: Option<String>,
+    pub can_see_nsfw: Option<bool>,
+    pub created: Option<DateTime<Utc>>,
+    pub email: Option<String>,
+    pub first_name: Option<String>,
+    pub full_name: Option<String>,
+    pub hide_pledges: Option<bool>,
+    pub image_url: Option<String>,
+    pub is_creator: Option<bool>,
+    pub is_email_verified: Option<bool>,
+    pub last_name: Option<String>,
+    pub like_count: Option<i64>,
+    pub social_connections: Option<serde_json::Value>,
+    pub thumb_url: Option<String>,
+    pub url: Option<String>,
+    pub vanity: Option<String>,
+    // v1 only
     pub facebook_id: Option<String>,
-    pub image_url: String,
-    pub thumb_url: String,
     pub youtube: Option<String>,
     pub twitter: Option<String>,
     pub facebook: Option<String>,
-    pub created: DateTime<Utc>,
-    pub url: String,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MemberAttributes {
-    pub campaign_lifetime_support_cents: i64,
-    pub currently_entitled_amount_cents: i64,
-    pub full_name: String,
-    pub is_follower: bool,
-    pub last_charge_date: DateTime<Utc>,
+    pub campaign_lifetime_support_cents: Option<i64>,
+    pub currently_entitled_amount_cents: Option<i64>,
+    pub email: Option<String>,
+    pub full_name: Option<String>,
+    pub is_follower: Option<bool>,
+    pub is_free_trial: Option<bool>,
+    pub is_gifted: Option<bool>,
+    pub last_charge_date: Option<DateTime<Utc>>,
     pub last_charge_status: Option<LastChrgeStatus>,
-    pub lifetime_support_cents: i64,
-    pub next_charge_date: DateTime<Utc>,
-    pub note: String,
+    pub lifetime_support_cents: Option<i64>,
+    pub next_charge_date: Option<DateTime<Utc>>,
+    pub note: Option<String>,
     pub patron_status: Option<PatronStatus>,
-    pub pledge_cadence: i64,
-    pub pledge_relationship_start: DateTime<Utc>,
-    pub will_pay_amount_cents: i64,
+    pub pledge_cadence: Option<i64>,
+    pub pledge_relationship_start: Option<DateTime<Utc>>,
+    pub will_pay_amount_cents: Option<i64>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CampaignMemberAttributes {
     pub patron_status: Option<PatronStatus>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CampaignAttributes {
-    pub created_at: DateTime<Utc>,
-    pub creation_name: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub creation_name: Option<String>,
     pub discord_server_id: Option<String>,
     pub google_analytics_id: Option<String>,
-    pub has_rss: bool,
-    pub has_sent_rss_notify: bool,
-    pub image_small_url: String,
+    pub has_rss: Option<bool>,
+    pub has_sent_rss_notify: Option<bool>,
+    pub image_small_url: Option<String>,
     pub image_url: Option<String>,
-    pub is_charged_immediately: bool,
-    pub is_monthly: bool,
-    pub is_nsfw: bool,
+    pub is_charged_immediately: Option<bool>,
+    pub is_monthly: Option<bool>,
+    pub is_nsfw: Option<bool>,
     pub main_video_embed: Option<String>,
     pub main_video_url: Option<String>,
     pub one_liner: Option<String>,
-    pub patron_count: i64,
-    pub pay_per_name: String,
-    pub pledge_url: String,
+    pub patron_count: Option<i64>,
+    pub pay_per_name: Option<String>,
+    pub pledge_url: Option<String>,
     pub published_at: Option<DateTime<Utc>>,
     pub rss_artwork_url: Option<String>,
     pub rss_feed_title: Option<String>,
@@ -224,8 +1198,177 @@ pub struct CampaignAttributes {
     pub thanks_embed: Option<String>,
     pub thanks_msg: Option<String>,
     pub thanks_video_url: Option<String>,
-    pub url: String,
-    pub vanity: String,
+    pub url: Option<String>,
+    pub vanity: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TierAttributes {
+    pub amount_cents: Option<i64>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+    pub discord_role_ids: Option<Vec<String>>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub image_url: Option<String>,
+    pub patron_count: Option<i64>,
+    pub post_count: Option<i64>,
+    pub published: Option<bool>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub remaining: Option<i64>,
+    pub requires_shipping: Option<bool>,
+    pub title: Option<String>,
+    pub unpublished_at: Option<DateTime<Utc>>,
+    pub url: Option<String>,
+    pub user_limit: Option<i64>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenefitAttributes {
+    pub app_external_id: Option<String>,
+    pub app_meta: Option<serde_json::Value>,
+    pub benefit_type: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub deliverables_due_today_count: Option<i64>,
+    pub delivered_deliverables_count: Option<i64>,
+    pub description: Option<String>,
+    pub is_deleted: Option<bool>,
+    pub is_ended: Option<bool>,
+    pub is_published: Option<bool>,
+    pub next_deliverable_due_date: Option<DateTime<Utc>>,
+    pub not_delivered_deliverables_count: Option<i64>,
+    pub rule_type: Option<String>,
+    pub tiers_count: Option<i64>,
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoalAttributes {
+    pub amount_cents: Option<i64>,
+    pub completed_percentage: Option<i64>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+    pub reached_at: Option<DateTime<Utc>>,
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostAttributes {
+    pub app_id: Option<i64>,
+    pub app_status: Option<String>,
+    pub content: Option<String>,
+    pub embed_data: Option<serde_json::Value>,
+    pub embed_url: Option<String>,
+    pub is_paid: Option<bool>,
+    pub is_public: Option<bool>,
+    #[serde(default, deserialize_with = "de_ids")]
+    pub tiers: Vec<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressAttributes {
+    pub addressee: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub line_1: Option<String>,
+    pub line_2: Option<String>,
+    pub phone_number: Option<String>,
+    pub postal_code: Option<String>,
+    pub state: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewardAttributes {
+    pub amount: Option<i64>,
+    pub amount_cents: Option<i64>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+    pub discord_role_ids: Option<Vec<String>>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub image_url: Option<String>,
+    pub patron_count: Option<i64>,
+    pub post_count: Option<i64>,
+    pub published: Option<bool>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub remaining: Option<i64>,
+    pub requires_shipping: Option<bool>,
+    pub title: Option<String>,
+    pub unpublished_at: Option<DateTime<Utc>>,
+    pub url: Option<String>,
+    pub user_limit: Option<i64>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PledgeEventAttributes {
+    pub amount_cents: Option<i64>,
+    pub currency_code: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub payment_status: Option<LastChrgeStatus>,
+    pub pledge_payment_status: Option<PledgePaymentStatus>,
+    pub tier_id: Option<String>,
+    pub tier_title: Option<String>,
+    #[serde(rename = "type")]
+    pub event_type: Option<PledgeEventType>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookAttributes {
+    pub last_attempted_at: Option<DateTime<Utc>>,
+    pub num_consecutive_times_failed: Option<i64>,
+    pub paused: Option<bool>,
+    pub secret: Option<String>,
+    pub triggers: Option<Vec<WebhookTrigger>>,
+    pub uri: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// Attributes to change with `update_webhook`, `None` leaves the attribute untouched.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub triggers: Option<Vec<WebhookTrigger>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused: Option<bool>,
+}
+
+// ids are sent as numbers in some attributes (e.g. post tiers) and as strings everywhere else
+fn de_ids<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+    let ids = Option::<Vec<serde_json::Value>>::deserialize(deserializer)?;
+    Ok(ids
+        .unwrap_or_default()
+        .into_iter()
+        .map(|id| match id {
+            serde_json::Value::String(id) => id,
+            other => other.to_string(),
+        })
+        .collect())
 }
 
 #[derive(Serialize, Deserialize)]
@@ -235,23 +1378,34 @@ struct ApiErrorResponse {
 
 macro_rules! enum_str {
     ($name:ident { $($variant:ident($str:expr), )* }) => {
-        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        #[derive(Clone, Debug, Eq, PartialEq, Hash)]
         pub enum $name {
             $($variant,)*
+            /// A value this version of the crate does not know about.
+            Unknown(String),
         }
 
         impl $name {
-            pub fn as_str(&self) -> &'static str {
+            /// Borrows from `self`, `Unknown` holds its own string (which is also why these enums are not `Copy`).
+            pub fn as_str(&self) -> &str {
                 match self {
                     $( $name::$variant => $str, )*
+                    $name::Unknown(value) => value.as_str(),
                 }
             }
         }
 
         impl std::fmt::Display for $name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                match self {
-                    $( $name::$variant => write!(f,"{}",$str), )*
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                match value {
+                    $( $str => $name::$variant, )*
+                    _ => $name::Unknown(value.to_string()),
                 }
             }
         }
@@ -261,9 +1415,7 @@ macro_rules! enum_str {
                 where S: ::serde::Serializer,
             {
                 // 将枚举序列化为字符串。
-                serializer.serialize_str(match *self {
-                    $( $name::$variant => $str, )*
-                })
+                serializer.serialize_str(self.as_str())
             }
         }
 
@@ -283,16 +1435,11 @@ macro_rules! enum_str {
                     fn visit_str<E>(self, value: &str) -> Result<$name, E>
                         where E: ::serde::de::Error,
                     {
-                        match value {
-                            $( $str => Ok($name::$variant), )*
-                            _ => Err(E::invalid_value(::serde::de::Unexpected::Other(
-                                &format!("unknown {} variant: {}", stringify!($name), value)
-                            ), &self)),
-                        }
+                        Ok($name::from(value))
                     }
                 }
 
-                // 从字符串反序列化枚举。
+                // 从字符串反序列化枚举，未知的值保留为 Unknown。
                 deserializer.deserialize_str(Visitor)
             }
         }
@@ -306,9 +1453,40 @@ macro_rules! enum_str {
     };
 }
 
+pub(crate) use enum_str;
+
 enum_str!(IdentityIncldue {
     Memberships("memberships"),
     Campaign("campaign"),
+    MembershipsCampaign("memberships.campaign"),
+    MembershipsCurrentlyEntitledTiers("memberships.currently_entitled_tiers"),
+});
+
+enum_str!(CampaignInclude {
+    Tiers("tiers"),
+    Benefits("benefits"),
+    Goals("goals"),
+    Creator("creator"),
+});
+
+enum_str!(MemberInclude {
+    Address("address"),
+    CurrentlyEntitledTiers("currently_entitled_tiers"),
+    User("user"),
+    Campaign("campaign"),
+    PledgeHistory("pledge_history"),
+});
+
+enum_str!(WebhookTrigger {
+    MembersCreate("members:create"),
+    MembersUpdate("members:update"),
+    MembersDelete("members:delete"),
+    MembersPledgeCreate("members:pledge:create"),
+    MembersPledgeUpdate("members:pledge:update"),
+    MembersPledgeDelete("members:pledge:delete"),
+    PostsPublish("posts:publish"),
+    PostsUpdate("posts:update"),
+    PostsDelete("posts:delete"),
 });
 
 enum_str!(LastChrgeStatus {
@@ -327,14 +1505,281 @@ enum_str!(PatronStatus {
     FormerPatron("former_patron"),
 });
 
+enum_str!(PledgeEventType {
+    PledgeStart("pledge_start"),
+    PledgeUpgrade("pledge_upgrade"),
+    PledgeDowngrade("pledge_downgrade"),
+    PledgeDelete("pledge_delete"),
+    Subscription("subscription"),
+});
+
+enum_str!(PledgePaymentStatus {
+    Queued("queued"),
+    Pending("pending"),
+    Valid("valid"),
+    Declined("declined"),
+    Fraud("fraud"),
+    Disabled("disabled"),
+});
+
 pub type Pledge = ApiDocument<PledgeAttributes>;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PledgeAttributes {
-    pub amount_cents: i64,
-    pub created_at: DateTime<Utc>,
-    pub currency: String,
+    pub amount_cents: Option<i64>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub currency: Option<String>,
     pub declined_since: Option<DateTime<Utc>>,
-    pub patron_pays_fees: bool,
-    pub pledge_cap_cents: i64,
+    pub patron_pays_fees: Option<bool>,
+    pub pledge_cap_cents: Option<i64>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoRetry;
+
+    fn page(links_next: Option<&str>, cursor: Option<&str>) -> Document<Vec<Campaign>> {
+        Document {
+            links: links_next.map(|next| Links {
+                next: Some(next.to_string()),
+                ..Default::default()
+            }),
+            meta: cursor.map(
+                |cursor| serde_json::json!({ "pagination": { "cursors": { "next": cursor } } }),
+            ),
+            data: vec![],
+            included: vec![],
+        }
+    }
+
+    #[test]
+    fn next_url_stays_on_base_url() {
+        let base = join_path(
+            &Url::parse("http://127.0.0.1:8080/gw").unwrap(),
+            "api/oauth2/v2/campaigns",
+        );
+        let next = page(
+            Some("https://www.patreon.com/api/oauth2/v2/campaigns?page%5Bcursor%5D=abc"),
+            None,
+        )
+        .next_url(&base)
+        .unwrap();
+        assert_eq!(
+            next.as_str(),
+            "http://127.0.0.1:8080/gw/api/oauth2/v2/campaigns?page%5Bcursor%5D=abc"
+        );
+    }
+
+    #[test]
+    fn retry_after_seconds_or_date() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(reqwest::header::RETRY_AFTER, "86400".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(86400)));
+        let date = (Utc::now() + chrono::Duration::hours(2)).to_rfc2822();
+        headers.insert(reqwest::header::RETRY_AFTER, date.parse().unwrap());
+        let wait = retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_secs(7100) && wait <= Duration::from_secs(7200));
+    }
+
+    #[test]
+    fn next_url_from_cursor() {
+        let base = Url::parse("https://www.patreon.com/api/oauth2/v2/campaigns").unwrap();
+        let next = page(None, Some("abc")).next_url(&base).unwrap();
+        assert_eq!(next.query(), Some("page%5Bcursor%5D=abc"));
+        assert_eq!(page(None, None).next_url(&base), None);
+    }
+
+    #[tokio::test]
+    async fn missing_scope_fails_before_sending() {
+        let api = PatreonApi::builder()
+            .access_token("token")
+            .base_url("http://127.0.0.1:9/")
+            .granted_scopes(ScopeSet::from([Scope::Identity]))
+            .build()
+            .unwrap();
+        match api.campaigns().await {
+            Err(PatreonError::MissingScope {
+                required,
+                granted,
+                errors,
+            }) => {
+                assert_eq!(required, ScopeSet::from([Scope::Campaigns]));
+                assert_eq!(granted, Some(ScopeSet::from([Scope::Identity])));
+                assert!(errors.is_empty());
+            }
+            result => panic!("expected MissingScope, got {result:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn v1_endpoints_accept_v2_scopes() {
+        // nothing listens here, reaching the network shows the scope check passed
+        let api = PatreonApi::builder()
+            .access_token("token")
+            .base_url("http://127.0.0.1:9/")
+            .retry_policy(NoRetry)
+            .granted_scopes(ScopeSet::from([Scope::Identity, Scope::Campaigns]))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            api.current_user().await,
+            Err(PatreonError::Reqwest(_))
+        ));
+    }
+
+    #[test]
+    fn enum_str_keeps_unknown_values() {
+        let status: PatronStatus = serde_json::from_str("\"paused_patron\"").unwrap();
+        assert_eq!(status, PatronStatus::Unknown("paused_patron".to_string()));
+        assert_eq!(serde_json::to_string(&status).unwrap(), "\"paused_patron\"");
+        let status: PatronStatus = serde_json::from_str("\"active_patron\"").unwrap();
+        assert_eq!(status, PatronStatus::ActivePatron);
+        assert_eq!(serde_json::to_string(&status).unwrap(), "\"active_patron\"");
+
+        let charge: LastChrgeStatus = serde_json::from_str("\"Chargeback\"").unwrap();
+        assert_eq!(charge, LastChrgeStatus::Unknown("Chargeback".to_string()));
+        assert_eq!(serde_json::to_string(&charge).unwrap(), "\"Chargeback\"");
+        let charge: LastChrgeStatus = serde_json::from_str("\"Paid\"").unwrap();
+        assert_eq!(charge, LastChrgeStatus::Paid);
+        assert_eq!(charge.as_str(), "Paid");
+    }
+
+    #[test]
+    fn included_resources_dispatch_on_type() {
+        let document: Document<User> = serde_json::from_value(serde_json::json!({
+            "data": { "type": "user", "id": "1" },
+            "included": [
+                { "type": "member", "id": "2", "attributes": { "patron_status": "active_patron" } },
+                { "type": "campaign", "id": "3", "attributes": { "patron_count": 10 } },
+                { "type": "tier", "id": "4", "attributes": { "title": "Gold" } },
+                { "type": "media", "id": "5", "attributes": { "file_name": "a.png" } }
+            ]
+        }))
+        .unwrap();
+        let included = &document.included;
+        assert!(matches!(&included[0], Resource::Member(member) if member.id == "2"));
+        assert!(
+            matches!(&included[1], Resource::Campaign(campaign) if campaign.attributes.patron_count == Some(10))
+        );
+        assert!(matches!(&included[2], Resource::Tier(tier) if tier.id == "4"));
+        assert!(
+            matches!(&included[3], Resource::Raw(raw) if raw["attributes"]["file_name"] == "a.png")
+        );
+        assert_eq!(included[3].resource_type(), "media");
+
+        assert!(Campaign::try_from(included[1].clone()).is_ok());
+        let err = Campaign::try_from(included[2].clone()).unwrap_err();
+        assert!(
+            matches!(err, PatreonError::Message(msg) if msg == "expected campaign resource, found tier")
+        );
+
+        let bad = serde_json::json!({
+            "data": { "type": "user", "id": "1" },
+            "included": [{ "type": "campaign", "id": "3", "attributes": { "patron_count": "many" } }]
+        });
+        assert!(serde_json::from_value::<Document<User>>(bad).is_err());
+    }
+
+    #[test]
+    fn related_resolves_included_resources() {
+        let document: Document<User> = serde_json::from_value(serde_json::json!({
+            "data": {
+                "type": "user",
+                "id": "1",
+                "relationships": {
+                    "memberships": { "data": [
+                        { "type": "member", "id": "2" },
+                        { "type": "member", "id": "3" },
+                        { "type": "member", "id": "404" }
+                    ] },
+                    "campaign": { "data": null }
+                }
+            },
+            "included": [
+                {
+                    "type": "member",
+                    "id": "2",
+                    "relationships": { "campaign": { "data": { "type": "campaign", "id": "10" } } }
+                },
+                {
+                    "type": "member",
+                    "id": "3",
+                    "relationships": { "campaign": { "data": null } }
+                },
+                { "type": "campaign", "id": "10" }
+            ]
+        }))
+        .unwrap();
+        let memberships = document
+            .related_as::<Member, _>(&document.data, "memberships")
+            .unwrap();
+        let ids = memberships
+            .iter()
+            .map(|member| member.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["2", "3"]);
+        let campaigns = memberships
+            .iter()
+            .map(|member| {
+                document
+                    .related_as::<Campaign, _>(member, "campaign")
+                    .unwrap()
+                    .pop()
+            })
+            .map(|campaign| campaign.map(|campaign| campaign.id))
+            .collect::<Vec<_>>();
+        assert_eq!(campaigns, [Some("10".to_string()), None]);
+        assert!(document.related(&document.data, "campaign").is_empty());
+        assert!(document.related(&document.data, "pledges").is_empty());
+    }
+
+    #[test]
+    fn follower_member_with_nulls() {
+        let member: Member = serde_json::from_value(serde_json::json!({
+            "type": "member",
+            "id": "2",
+            "attributes": {
+                "full_name": "Follower",
+                "is_follower": true,
+                "last_charge_date": null,
+                "last_charge_status": null,
+                "note": null,
+                "patron_status": null,
+                "campaign_currency": "USD"
+            }
+        }))
+        .unwrap();
+        let attributes = member.attributes;
+        assert_eq!(attributes.is_follower, Some(true));
+        assert_eq!(attributes.last_charge_date, None);
+        assert_eq!(attributes.next_charge_date, None);
+        assert_eq!(attributes.note, None);
+        assert_eq!(attributes.patron_status, None);
+        assert_eq!(attributes.extra.len(), 1);
+        assert_eq!(attributes.extra["campaign_currency"], "USD");
+    }
+
+    #[test]
+    fn build_rejects_invalid_user_agent() {
+        for user_agent in ["", "bad\nua"] {
+            let result = PatreonApi::builder()
+                .access_token("token")
+                .user_agent(user_agent)
+                .build();
+            assert!(
+                matches!(result, Err(PatreonError::Message(_))),
+                "{user_agent:?}"
+            );
+        }
+        let api = PatreonApi::builder()
+            .access_token("token")
+            .user_agent("my-integration/1.0")
+            .build()
+            .unwrap();
+        assert_eq!(api.user_agent, "my-integration/1.0");
+    }
 }
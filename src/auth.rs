@@ -0,0 +1,261 @@
+use crate::{PatreonApi, PatreonError, PatreonOAuth, PatreonResult, TokenStore, Tokens};
+use reqwest::StatusCode;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+type OnRefresh = Arc<dyn Fn(&Tokens) + Send + Sync>;
+
+/// A `PatreonApi` that owns its tokens and refreshes them with a `PatreonOAuth`,
+/// shortly before they expire and again when a request is rejected with 401.
+pub struct AuthenticatedApi {
+    api: PatreonApi,
+    oauth: PatreonOAuth,
+    refresh_margin: Duration,
+    on_refresh: Option<OnRefresh>,
+    store: Option<(Arc<dyn TokenStore>, String)>,
+    state: Mutex<TokenState>,
+}
+
+struct TokenState {
+    tokens: Tokens,
+    api: Arc<PatreonApi>,
+}
+
+impl AuthenticatedApi {
+    /// `api` is used as a template for every request, its `access_token` is replaced by the one in `tokens`.
+    pub fn new(api: PatreonApi, oauth: PatreonOAuth, tokens: Tokens) -> Self {
+        let state = TokenState::new(&api, tokens);
+        Self {
+            api,
+            oauth,
+            refresh_margin: Duration::from_secs(300),
+            on_refresh: None,
+            store: None,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Starts from the tokens saved under `key`, refreshed tokens are saved back to `store`.
+    pub async fn from_store(
+        api: PatreonApi,
+        oauth: PatreonOAuth,
+        store: Arc<dyn TokenStore>,
+        key: impl Into<String>,
+    ) -> PatreonResult<Self> {
+        let key = key.into();
+        let tokens = store
+            .load(key.as_str())
+            .await?
+            .ok_or_else(|| PatreonError::Message(format!("no tokens stored for {key}")))?;
+        Ok(Self::new(api, oauth, tokens).token_store(store, key))
+    }
+
+    /// Saves the tokens under `key` after every refresh.
+    pub fn token_store(mut self, store: Arc<dyn TokenStore>, key: impl Into<String>) -> Self {
+        self.store = Some((store, key.into()));
+        self
+    }
+
+    /// How long before `expires_at` the tokens are refreshed, defaults to 5 minutes.
+    pub fn refresh_margin(mut self, refresh_margin: Duration) -> Self {
+        self.refresh_margin = refresh_margin;
+        self
+    }
+
+    /// Called with the new tokens after every refresh, before they are saved to the token store.
+    pub fn on_refresh(mut self, on_refresh: impl Fn(&Tokens) + Send + Sync + 'static) -> Self {
+        self.on_refresh = Some(Arc::new(on_refresh));
+        self
+    }
+
+    pub async fn tokens(&self) -> Tokens {
+        self.state.lock().await.tokens.clone()
+    }
+
+    /// A client holding a valid access token, refreshed first when it is about to expire.
+    /// Useful for streams, a 401 from the returned client is not handled.
+    pub async fn api(&self) -> PatreonResult<Arc<PatreonApi>> {
+        let mut state = self.state.lock().await;
+        if state
+            .tokens
+            .expires_within(self.refresh_margin, self.oauth.clock.as_ref())
+        {
+            self.refresh(&mut state).await?;
+        }
+        Ok(state.api.clone())
+    }
+
+    /// Runs `call` with a valid client, on 401 the tokens are refreshed and `call` runs once more.
+    pub async fn call<T, F, Fut>(&self, call: F) -> PatreonResult<T>
+    where
+        F: Fn(Arc<PatreonApi>) -> Fut,
+        Fut: Future<Output = PatreonResult<T>>,
+    {
+        let api = self.api().await?;
+        match call(api.clone()).await {
+            Err(PatreonError::PatreonApi(StatusCode::UNAUTHORIZED, _)) => {
+                let mut state = self.state.lock().await;
+                // another call may have refreshed while this one was in flight
+                if state.api.access_token == api.access_token {
+                    self.refresh(&mut state).await?;
+                }
+                let api = state.api.clone();
+                drop(state);
+                call(api).await
+            }
+            result => result,
+        }
+    }
+
+    async fn refresh(&self, state: &mut TokenState) -> PatreonResult<()> {
+        let tokens = self
+            .oauth
+            .refresh_tokens(state.tokens.refresh_token.as_str())
+            .await?;
+        *state = TokenState::new(&self.api, tokens);
+        // the old refresh token is spent, the callback must see the new one even if saving fails
+        if let Some(on_refresh) = &self.on_refresh {
+            on_refresh(&state.tokens);
+        }
+        if let Some((store, key)) = &self.store {
+            store.save(key.as_str(), &state.tokens).await?;
+        }
+        Ok(())
+    }
+}
+
+impl TokenState {
+    fn new(api: &PatreonApi, tokens: Tokens) -> Self {
+        let granted_scopes = (!tokens.scope.is_empty()).then(|| tokens.scope.clone());
+        let api = PatreonApi {
+            access_token: tokens.access_token.clone(),
+            granted_scopes: granted_scopes.or_else(|| api.granted_scopes.clone()),
+            ..api.clone()
+        };
+        Self {
+            api: Arc::new(api),
+            tokens,
+        }
+    }
+}
+
+impl Debug for AuthenticatedApi {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthenticatedApi")
+            .field("api", &self.api)
+            .field("oauth", &self.oauth)
+            .field("refresh_margin", &self.refresh_margin)
+            .field("store", &self.store)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oauth2::tests::{issued_at, FixedClock};
+    use crate::NoRetry;
+
+    fn authenticated(now_offset: i64) -> AuthenticatedApi {
+        // nothing listens here, so a refresh fails instead of reaching patreon
+        authenticated_on(now_offset, "http://127.0.0.1:9/")
+    }
+
+    fn authenticated_on(now_offset: i64, base_url: &str) -> AuthenticatedApi {
+        let clock = FixedClock(issued_at() + chrono::Duration::seconds(now_offset));
+        let api = PatreonApi::builder().access_token("old").build().unwrap();
+        let oauth = PatreonOAuth::builder()
+            .client_id("id")
+            .client_secret("secret")
+            .redirect_uri("http://localhost/callback")
+            .base_url(base_url)
+            .retry_policy(NoRetry)
+            .clock(clock)
+            .build()
+            .unwrap();
+        let tokens = Tokens {
+            access_token: "old".to_string(),
+            expires_at: issued_at() + chrono::Duration::seconds(3600),
+            ..Default::default()
+        };
+        AuthenticatedApi::new(api, oauth, tokens).refresh_margin(Duration::from_secs(300))
+    }
+
+    #[tokio::test]
+    async fn valid_tokens_are_not_refreshed() {
+        let api = authenticated(3299).api().await.unwrap();
+        assert_eq!(api.access_token, "old");
+    }
+
+    #[tokio::test]
+    async fn tokens_within_margin_are_refreshed() {
+        for now_offset in [3300, 3600] {
+            let result = authenticated(now_offset).api().await;
+            assert!(matches!(result, Err(PatreonError::Reqwest(_))));
+        }
+    }
+
+    /// Answers one token request with `new` tokens, returns the base url to send it to.
+    async fn token_server() -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !String::from_utf8_lossy(&request).contains("refresh_token=") {
+                let read = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..read]);
+            }
+            let body = r#"{"access_token":"new","refresh_token":"rotated","expires_in":3600,"token_type":"Bearer","scope":"identity","version":"0.0.1"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        base_url
+    }
+
+    #[derive(Debug)]
+    struct FailingStore;
+
+    impl TokenStore for FailingStore {
+        fn load<'a>(
+            &'a self,
+            _: &'a str,
+        ) -> futures::future::BoxFuture<'a, PatreonResult<Option<Tokens>>> {
+            Box::pin(async { Ok(None) })
+        }
+
+        fn save<'a>(
+            &'a self,
+            _: &'a str,
+            _: &'a Tokens,
+        ) -> futures::future::BoxFuture<'a, PatreonResult<()>> {
+            Box::pin(async { Err(PatreonError::Message("disk full".to_string())) })
+        }
+
+        fn remove<'a>(&'a self, _: &'a str) -> futures::future::BoxFuture<'a, PatreonResult<()>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn on_refresh_runs_when_saving_fails() {
+        let seen = Arc::new(std::sync::Mutex::new(None));
+        let on_refresh = seen.clone();
+        let api = authenticated_on(3600, token_server().await.as_str())
+            .token_store(Arc::new(FailingStore), "creator")
+            .on_refresh(move |tokens| {
+                *on_refresh.lock().unwrap() = Some(tokens.refresh_token.clone())
+            });
+        assert!(matches!(api.api().await, Err(PatreonError::Message(_))));
+        assert_eq!(seen.lock().unwrap().as_deref(), Some("rotated"));
+        assert_eq!(api.tokens().await.access_token, "new");
+    }
+}
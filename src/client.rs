@@ -0,0 +1,127 @@
+use crate::{ExponentialBackoff, PatreonError, PatreonResult, RateLimiter, RetryPolicy};
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+pub(crate) static DEFAULT_USER_AGENT: &str = "Patreon-rust";
+
+/// Http options shared by `PatreonApiBuilder` and `PatreonOAuthBuilder`.
+#[derive(Debug, Default)]
+pub(crate) struct ClientOptions {
+    pub(crate) base_url: Option<String>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) user_agent: Option<String>,
+    pub(crate) proxy: Option<reqwest::Proxy>,
+    pub(crate) root_certificates: Vec<reqwest::Certificate>,
+    pub(crate) retry_policy: Option<Arc<dyn RetryPolicy>>,
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+pub(crate) fn default_retry_policy() -> Arc<dyn RetryPolicy> {
+    Arc::new(ExponentialBackoff::default())
+}
+
+impl ClientOptions {
+    pub(crate) fn base_url(&self, default: &str) -> PatreonResult<Url> {
+        let base_url = self.base_url.as_deref().unwrap_or(default);
+        Url::parse(base_url)
+            .map_err(|err| PatreonError::Message(format!("invalid base_url {base_url}: {err}")))
+    }
+
+    pub(crate) fn user_agent(&self) -> PatreonResult<String> {
+        match self.user_agent.as_deref() {
+            None => Ok(DEFAULT_USER_AGENT.to_string()),
+            Some("") => Err(PatreonError::Message(
+                "user_agent must not be empty".to_string(),
+            )),
+            Some(user_agent) => reqwest::header::HeaderValue::from_str(user_agent)
+                .map(|_| user_agent.to_string())
+                .map_err(|err| {
+                    PatreonError::Message(format!("invalid user_agent {user_agent:?}: {err}"))
+                }),
+        }
+    }
+
+    pub(crate) fn retry_policy(&self) -> Arc<dyn RetryPolicy> {
+        self.retry_policy
+            .clone()
+            .unwrap_or_else(default_retry_policy)
+    }
+
+    pub(crate) fn agent(self) -> PatreonResult<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(proxy) = self.proxy {
+            builder = builder.proxy(proxy);
+        }
+        for certificate in self.root_certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// Fails with the name of the first field that is missing or empty.
+pub(crate) fn required(name: &str, value: Option<String>) -> PatreonResult<String> {
+    match value {
+        Some(value) if !value.is_empty() => Ok(value),
+        _ => Err(PatreonError::Message(format!("{name} is required"))),
+    }
+}
+
+/// Setters of the `options: ClientOptions` field, shared by both builders.
+macro_rules! client_options_setters {
+    () => {
+        pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+            self.options.base_url = Some(base_url.into());
+            self
+        }
+
+        /// Timeout of a whole request, from connecting until the body is read.
+        pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+            self.options.timeout = Some(timeout);
+            self
+        }
+
+        pub fn connect_timeout(mut self, connect_timeout: std::time::Duration) -> Self {
+            self.options.connect_timeout = Some(connect_timeout);
+            self
+        }
+
+        /// Patreon asks integrations to identify themselves, defaults to `Patreon-rust`.
+        pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+            self.options.user_agent = Some(user_agent.into());
+            self
+        }
+
+        pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+            self.options.proxy = Some(proxy);
+            self
+        }
+
+        pub fn add_root_certificate(mut self, certificate: reqwest::Certificate) -> Self {
+            self.options.root_certificates.push(certificate);
+            self
+        }
+
+        /// Retries of failed GETs and token refreshes, defaults to `ExponentialBackoff`.
+        pub fn retry_policy(mut self, retry_policy: impl $crate::RetryPolicy + 'static) -> Self {
+            self.options.retry_policy = Some(std::sync::Arc::new(retry_policy));
+            self
+        }
+
+        /// Share the `Arc` to limit several clients together.
+        pub fn rate_limiter(mut self, rate_limiter: std::sync::Arc<$crate::RateLimiter>) -> Self {
+            self.options.rate_limiter = Some(rate_limiter);
+            self
+        }
+    };
+}
+
+pub(crate) use client_options_setters;
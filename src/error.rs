@@ -1,6 +1,8 @@
+use crate::ScopeSet;
 use reqwest::StatusCode;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt::{Debug, Display, Formatter};
+use std::time::Duration;
 
 pub type PatreonResult<A> = std::result::Result<A, PatreonError>;
 
@@ -10,6 +12,17 @@ pub enum PatreonError {
     SerdeJson(serde_json::Error),
     PatreonOAuth(StatusCode, String),
     PatreonApi(StatusCode, Vec<ApiError>),
+    /// 429 from the api, with the `Retry-After` the server asked for.
+    RateLimited(Option<Duration>),
+    /// A stored token record could not be decrypted (unknown key, tampered or corrupt record).
+    TokenDecryption(String),
+    /// The call needs scopes the access token was not granted, `granted` is `None` when the
+    /// scopes are unknown, `errors` holds the body of the 403 when the api rejected the call.
+    MissingScope {
+        required: ScopeSet,
+        granted: Option<ScopeSet>,
+        errors: Vec<ApiError>,
+    },
     Message(String),
 }
 
@@ -30,6 +43,27 @@ impl Display for PatreonError {
                 f.write_str(" ] }")?;
                 Ok(())
             }
+            PatreonError::RateLimited(retry_after) => {
+                write!(f, "RateLimited {{ retry_after : {retry_after:?} }}")
+            }
+            PatreonError::TokenDecryption(msg) => write!(f, "TokenDecryption ( {msg} )"),
+            PatreonError::MissingScope {
+                required,
+                granted,
+                errors,
+            } => {
+                write!(f, "MissingScope {{ required : [ {required} ], granted : ")?;
+                match granted {
+                    Some(granted) => write!(f, "[ {granted} ]")?,
+                    None => f.write_str("unknown")?,
+                }
+                f.write_str(", errors : [ ")?;
+                for x in errors {
+                    Display::fmt(x, f)?;
+                    f.write_str(", ")?;
+                }
+                f.write_str(" ] }")
+            }
             PatreonError::Message(msg) => {
                 write!(f, "Message ( {msg} ) ,")
             }
@@ -37,6 +71,39 @@ impl Display for PatreonError {
     }
 }
 
+impl PatreonError {
+    /// Timeouts, failed connects, dropped connections and 502 / 503 / 504 responses, which may
+    /// succeed when sent again. Other request errors (TLS, certificates, bad urls) are not retried.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            PatreonError::Reqwest(err) => {
+                err.is_timeout() || err.is_connect() || err.is_body() || is_connection_reset(err)
+            }
+            PatreonError::PatreonApi(status, _) | PatreonError::PatreonOAuth(status, _) => {
+                matches!(status.as_u16(), 502..=504)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn is_connection_reset(err: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            return matches!(
+                err.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = err.source();
+    }
+    false
+}
+
 impl std::error::Error for PatreonError {}
 
 impl From<reqwest::Error> for PatreonError {
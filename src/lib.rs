@@ -1,10 +1,25 @@
 pub use api::*;
+pub use auth::*;
+pub use clock::*;
 pub use error::*;
 pub use oauth2::*;
+pub use query::*;
+pub use rate_limit::*;
+pub use retry::*;
+pub use scope::*;
+pub use token_store::*;
 pub use webhook::*;
 
 pub mod api;
+pub mod auth;
+mod client;
+pub mod clock;
 mod compile_rules;
 pub mod error;
 pub mod oauth2;
+pub mod query;
+pub mod rate_limit;
+pub mod retry;
+pub mod scope;
+pub mod token_store;
 pub mod webhook;
@@ -1,39 +1,122 @@
-use crate::{PatreonError, PatreonResult};
+use crate::api::{join_path, BASE_URI};
+use crate::client::{
+    client_options_setters, default_retry_policy, required, ClientOptions, DEFAULT_USER_AGENT,
+};
+use crate::{Clock, PatreonError, PatreonResult, RateLimiter, RetryPolicy, ScopeSet, SystemClock};
+use chrono::{DateTime, Utc};
 use reqwest::StatusCode;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use url::Url;
 
-static BASE_URI: &str = "https://www.patreon.com";
-
-#[derive(Debug, Default)]
+#[derive(Debug, Clone)]
 pub struct PatreonOAuth {
     pub client_id: String,
     pub client_secret: String,
     pub redirect_uri: String,
     pub agent: Arc<reqwest::Client>,
+    /// Root of the authorize and token paths, defaults to `https://www.patreon.com`.
+    pub base_url: Url,
+    pub user_agent: String,
+    /// Retries of failed token refreshes, exchanging a code is never retried.
+    pub retry_policy: Arc<dyn RetryPolicy>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Stamps `Tokens::expires_at`, defaults to `SystemClock`.
+    pub clock: Arc<dyn Clock>,
 }
 
-impl PatreonOAuth {
-    pub fn get_authorization_url(&self, scope: &str, state: &str) -> String {
-        let mut url = Url::parse(BASE_URI).unwrap();
-        url.set_path("/oauth2/authorize");
-        if !scope.is_empty() {
-            url.query_pairs_mut().append_pair("scope", scope);
-        }
-        if !state.is_empty() {
-            url.query_pairs_mut().append_pair("state", state);
+impl Default for PatreonOAuth {
+    fn default() -> Self {
+        Self {
+            client_id: String::default(),
+            client_secret: String::default(),
+            redirect_uri: String::default(),
+            agent: Arc::default(),
+            base_url: Url::parse(BASE_URI).unwrap(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            retry_policy: default_retry_policy(),
+            rate_limiter: None,
+            clock: Arc::new(SystemClock),
         }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PatreonOAuthBuilder {
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    redirect_uri: Option<String>,
+    clock: Option<Arc<dyn Clock>>,
+    options: ClientOptions,
+}
+
+impl PatreonOAuthBuilder {
+    pub fn client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = Some(client_id.into());
+        self
+    }
+
+    pub fn client_secret(mut self, client_secret: impl Into<String>) -> Self {
+        self.client_secret = Some(client_secret.into());
+        self
+    }
+
+    pub fn redirect_uri(mut self, redirect_uri: impl Into<String>) -> Self {
+        self.redirect_uri = Some(redirect_uri.into());
+        self
+    }
+
+    client_options_setters!();
+
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Fails when `client_id`, `client_secret` or `redirect_uri` are missing, or `base_url` / `user_agent` are invalid.
+    pub fn build(self) -> PatreonResult<PatreonOAuth> {
+        let redirect_uri = required("redirect_uri", self.redirect_uri)?;
+        Url::parse(redirect_uri.as_str()).map_err(|err| {
+            PatreonError::Message(format!("invalid redirect_uri {redirect_uri}: {err}"))
+        })?;
+        Ok(PatreonOAuth {
+            client_id: required("client_id", self.client_id)?,
+            client_secret: required("client_secret", self.client_secret)?,
+            redirect_uri,
+            base_url: self.options.base_url(BASE_URI)?,
+            user_agent: self.options.user_agent()?,
+            retry_policy: self.options.retry_policy(),
+            rate_limiter: self.options.rate_limiter.clone(),
+            clock: self.clock.unwrap_or_else(|| Arc::new(SystemClock)),
+            agent: Arc::new(self.options.agent()?),
+        })
+    }
+}
+
+impl PatreonOAuth {
+    pub fn builder() -> PatreonOAuthBuilder {
+        PatreonOAuthBuilder::default()
+    }
+
+    pub fn get_authorization_url(&self, scopes: &ScopeSet, state: &str) -> String {
+        let mut url = join_path(&self.base_url, "/oauth2/authorize");
         url.query_pairs_mut()
-            .append_pair("scope ", "campaigns")
             .append_pair("response_type", "code")
             .append_pair("client_id", self.client_id.as_str())
             .append_pair("redirect_uri", self.redirect_uri.as_str());
+        if !scopes.is_empty() {
+            url.query_pairs_mut()
+                .append_pair("scope", scopes.to_string().as_str());
+        }
+        if !state.is_empty() {
+            url.query_pairs_mut().append_pair("state", state);
+        }
         url.to_string()
     }
 
-    pub async fn get_tokens(&self, code: &str) -> PatreonResult<TokensResponse> {
+    pub async fn get_tokens(&self, code: &str) -> PatreonResult<Tokens> {
         self.parse_token_request(&{
             let mut params = HashMap::new();
             params.insert("grant_type", "authorization_code");
@@ -46,7 +129,7 @@ impl PatreonOAuth {
         .await
     }
 
-    pub async fn refresh_tokens(&self, refresh_token: &str) -> PatreonResult<TokensResponse> {
+    pub async fn refresh_tokens(&self, refresh_token: &str) -> PatreonResult<Tokens> {
         self.parse_token_request(&{
             let mut params = HashMap::new();
             params.insert("grant_type", "refresh_token");
@@ -58,13 +141,41 @@ impl PatreonOAuth {
         .await
     }
 
-    async fn parse_token_request(
-        &self,
-        params: &HashMap<&str, &str>,
-    ) -> PatreonResult<TokensResponse> {
-        let mut url = Url::parse(BASE_URI).unwrap();
-        url.set_path("/api/oauth2/token");
-        let response = self.agent.post(url).form(params).send().await?;
+    async fn parse_token_request(&self, params: &HashMap<&str, &str>) -> PatreonResult<Tokens> {
+        // an authorization code is single use, only refreshes are safe to send twice
+        let idempotent = params.get("grant_type") == Some(&"refresh_token");
+        let mut attempt = 0;
+        loop {
+            let result = self.token_request(params).await;
+            let wait = match &result {
+                Err(err) if idempotent => {
+                    attempt += 1;
+                    self.retry_policy.retry_after(attempt, err)
+                }
+                _ => None,
+            };
+            match wait {
+                Some(wait) => {
+                    tracing::debug!("RETRY : {:?} in {:?}", result.err(), wait);
+                    tokio::time::sleep(wait).await;
+                }
+                None => return Ok(Tokens::new(result?, self.clock.now())),
+            }
+        }
+    }
+
+    async fn token_request(&self, params: &HashMap<&str, &str>) -> PatreonResult<TokensResponse> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+        let url = join_path(&self.base_url, "/api/oauth2/token");
+        let response = self
+            .agent
+            .post(url)
+            .header("User-Agent", self.user_agent.as_str())
+            .form(params)
+            .send()
+            .await?;
         let status = response.status();
         let text = response.text().await?;
         de_response(status, text)
@@ -76,11 +187,55 @@ pub struct TokensResponse {
     pub access_token: String,
     pub expires_in: u64,
     pub token_type: String,
-    pub scope: String,
+    pub scope: ScopeSet,
     pub refresh_token: String,
     pub version: String,
 }
 
+/// Tokens with the absolute time they expire at, so they can be stored and checked later.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub scope: ScopeSet,
+    pub version: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Tokens {
+    /// `issued_at` is when `response` was received, `expires_in` counts from there.
+    pub fn new(response: TokensResponse, issued_at: DateTime<Utc>) -> Self {
+        let expires_at = i64::try_from(response.expires_in)
+            .ok()
+            .and_then(chrono::Duration::try_seconds)
+            .and_then(|expires_in| issued_at.checked_add_signed(expires_in))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        Self {
+            expires_at,
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+            token_type: response.token_type,
+            scope: response.scope,
+            version: response.version,
+        }
+    }
+
+    pub fn is_expired(&self, clock: &dyn Clock) -> bool {
+        self.expires_at <= clock.now()
+    }
+
+    /// Whether the tokens are expired or expire in `duration` or less.
+    pub fn expires_within(&self, duration: Duration, clock: &dyn Clock) -> bool {
+        let duration = chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+        self.expires_at
+            <= clock
+                .now()
+                .checked_add_signed(duration)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct ErrorResponse {
     pub error: String,
@@ -92,6 +247,11 @@ fn de_response<T: for<'de> serde::Deserialize<'de>>(
 ) -> PatreonResult<T> {
     if status.is_success() {
         Ok(serde_json::from_str(text.as_str())?)
+    } else if status.is_server_error() {
+        let error = serde_json::from_str::<ErrorResponse>(text.as_str())
+            .map(|response| response.error)
+            .unwrap_or(text);
+        Err(PatreonError::PatreonOAuth(status, error))
     } else {
         Err(PatreonError::PatreonOAuth(
             status,
@@ -99,3 +259,53 @@ fn de_response<T: for<'de> serde::Deserialize<'de>>(
         ))
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct FixedClock(pub DateTime<Utc>);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<Utc> {
+            self.0
+        }
+    }
+
+    pub(crate) fn issued_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn tokens(expires_in: u64) -> Tokens {
+        let response = TokensResponse {
+            expires_in,
+            ..Default::default()
+        };
+        Tokens::new(response, issued_at())
+    }
+
+    #[test]
+    fn tokens_expire_after_expires_in() {
+        let tokens = tokens(3600);
+        assert_eq!(tokens.expires_at, issued_at() + chrono::Duration::hours(1));
+        assert!(!tokens.is_expired(&FixedClock(issued_at())));
+        assert!(tokens.is_expired(&FixedClock(tokens.expires_at)));
+    }
+
+    #[test]
+    fn huge_expires_in_does_not_overflow() {
+        assert_eq!(tokens(u64::MAX).expires_at, DateTime::<Utc>::MAX_UTC);
+        assert_eq!(tokens(i64::MAX as u64).expires_at, DateTime::<Utc>::MAX_UTC);
+    }
+
+    #[test]
+    fn expires_within_margin() {
+        let tokens = tokens(3600);
+        let clock = FixedClock(issued_at());
+        assert!(!tokens.expires_within(Duration::from_secs(3599), &clock));
+        assert!(tokens.expires_within(Duration::from_secs(3600), &clock));
+        assert!(tokens.expires_within(Duration::MAX, &clock));
+    }
+}
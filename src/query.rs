@@ -0,0 +1,303 @@
+use crate::api::enum_str;
+use std::collections::BTreeMap;
+use url::Url;
+
+/// A sparse fieldset member of one JSON:API resource type.
+pub trait Field {
+    /// The resource type the field belongs to, used as `fields[RESOURCE]`.
+    const RESOURCE: &'static str;
+
+    fn name(&self) -> &str;
+}
+
+/// `fields[..]` and `include` parameters of a request.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    fields: BTreeMap<&'static str, Vec<String>>,
+    include: Vec<String>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds fields to the sparse fieldset of `F::RESOURCE`, duplicates are ignored.
+    pub fn fields<F: Field>(mut self, fields: &[F]) -> Self {
+        let selected = self.fields.entry(F::RESOURCE).or_default();
+        for field in fields {
+            if !selected.iter().any(|name| name == field.name()) {
+                selected.push(field.name().to_string());
+            }
+        }
+        self
+    }
+
+    /// Adds an include path, nested paths are dot separated (e.g. `memberships.campaign`).
+    pub fn include(mut self, path: impl Into<String>) -> Self {
+        let path = path.into();
+        if !self.include.contains(&path) {
+            self.include.push(path);
+        }
+        self
+    }
+
+    /// Whether `path` or a path nested below it (e.g. `memberships.campaign` for `memberships`) is included.
+    pub fn has_include(&self, path: &str) -> bool {
+        self.include.iter().any(|include| {
+            include == path
+                || include
+                    .strip_prefix(path)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.include.is_empty()
+    }
+
+    pub(crate) fn append_to(&self, url: &mut Url) {
+        if self.is_empty() {
+            return;
+        }
+        let mut pairs = url.query_pairs_mut();
+        if !self.include.is_empty() {
+            pairs.append_pair("include", self.include.join(",").as_str());
+        }
+        for (resource, fields) in &self.fields {
+            pairs.append_pair(
+                format!("fields[{}]", resource).as_str(),
+                fields.join(",").as_str(),
+            );
+        }
+    }
+}
+
+macro_rules! field_enum {
+    ($name:ident($resource:expr) { $($variant:ident($str:expr), )* }) => {
+        enum_str!($name {
+            $($variant($str),)*
+        });
+
+        impl $name {
+            pub const ALL: &'static [$name] = &[$($name::$variant,)*];
+        }
+
+        impl Field for $name {
+            const RESOURCE: &'static str = $resource;
+
+            fn name(&self) -> &str {
+                self.as_str()
+            }
+        }
+    };
+}
+
+field_enum!(UserField("user") {
+    About("about"),
+    CanSeeNsfw("can_see_nsfw"),
+    Created("created"),
+    Email("email"),
+    FirstName("first_name"),
+    FullName("full_name"),
+    HidePledges("hide_pledges"),
+    ImageUrl("image_url"),
+    IsCreator("is_creator"),
+    IsEmailVerified("is_email_verified"),
+    LastName("last_name"),
+    LikeCount("like_count"),
+    SocialConnections("social_connections"),
+    ThumbUrl("thumb_url"),
+    Url("url"),
+    Vanity("vanity"),
+});
+
+field_enum!(MemberField("member") {
+    CampaignLifetimeSupportCents("campaign_lifetime_support_cents"),
+    CurrentlyEntitledAmountCents("currently_entitled_amount_cents"),
+    Email("email"),
+    FullName("full_name"),
+    IsFollower("is_follower"),
+    IsFreeTrial("is_free_trial"),
+    IsGifted("is_gifted"),
+    LastChargeDate("last_charge_date"),
+    LastChargeStatus("last_charge_status"),
+    LifetimeSupportCents("lifetime_support_cents"),
+    NextChargeDate("next_charge_date"),
+    Note("note"),
+    PatronStatus("patron_status"),
+    PledgeCadence("pledge_cadence"),
+    PledgeRelationshipStart("pledge_relationship_start"),
+    WillPayAmountCents("will_pay_amount_cents"),
+});
+
+field_enum!(CampaignField("campaign") {
+    CreatedAt("created_at"),
+    CreationName("creation_name"),
+    DiscordServerId("discord_server_id"),
+    GoogleAnalyticsId("google_analytics_id"),
+    HasRss("has_rss"),
+    HasSentRssNotify("has_sent_rss_notify"),
+    ImageSmallUrl("image_small_url"),
+    ImageUrl("image_url"),
+    IsChargedImmediately("is_charged_immediately"),
+    IsMonthly("is_monthly"),
+    IsNsfw("is_nsfw"),
+    MainVideoEmbed("main_video_embed"),
+    MainVideoUrl("main_video_url"),
+    OneLiner("one_liner"),
+    PatronCount("patron_count"),
+    PayPerName("pay_per_name"),
+    PledgeUrl("pledge_url"),
+    PublishedAt("published_at"),
+    RssArtworkUrl("rss_artwork_url"),
+    RssFeedTitle("rss_feed_title"),
+    ShowEarnings("show_earnings"),
+    Summary("summary"),
+    ThanksEmbed("thanks_embed"),
+    ThanksMsg("thanks_msg"),
+    ThanksVideoUrl("thanks_video_url"),
+    Url("url"),
+    Vanity("vanity"),
+});
+
+field_enum!(TierField("tier") {
+    AmountCents("amount_cents"),
+    CreatedAt("created_at"),
+    Description("description"),
+    DiscordRoleIds("discord_role_ids"),
+    EditedAt("edited_at"),
+    ImageUrl("image_url"),
+    PatronCount("patron_count"),
+    PostCount("post_count"),
+    Published("published"),
+    PublishedAt("published_at"),
+    Remaining("remaining"),
+    RequiresShipping("requires_shipping"),
+    Title("title"),
+    UnpublishedAt("unpublished_at"),
+    Url("url"),
+    UserLimit("user_limit"),
+});
+
+field_enum!(BenefitField("benefit") {
+    AppExternalId("app_external_id"),
+    AppMeta("app_meta"),
+    BenefitType("benefit_type"),
+    CreatedAt("created_at"),
+    DeliverablesDueTodayCount("deliverables_due_today_count"),
+    DeliveredDeliverablesCount("delivered_deliverables_count"),
+    Description("description"),
+    IsDeleted("is_deleted"),
+    IsEnded("is_ended"),
+    IsPublished("is_published"),
+    NextDeliverableDueDate("next_deliverable_due_date"),
+    NotDeliveredDeliverablesCount("not_delivered_deliverables_count"),
+    RuleType("rule_type"),
+    TiersCount("tiers_count"),
+    Title("title"),
+});
+
+field_enum!(GoalField("goal") {
+    AmountCents("amount_cents"),
+    CompletedPercentage("completed_percentage"),
+    CreatedAt("created_at"),
+    Description("description"),
+    ReachedAt("reached_at"),
+    Title("title"),
+});
+
+field_enum!(AddressField("address") {
+    Addressee("addressee"),
+    City("city"),
+    Country("country"),
+    CreatedAt("created_at"),
+    Line1("line_1"),
+    Line2("line_2"),
+    PhoneNumber("phone_number"),
+    PostalCode("postal_code"),
+    State("state"),
+});
+
+field_enum!(PledgeEventField("pledge-event") {
+    AmountCents("amount_cents"),
+    CurrencyCode("currency_code"),
+    Date("date"),
+    PaymentStatus("payment_status"),
+    PledgePaymentStatus("pledge_payment_status"),
+    TierId("tier_id"),
+    TierTitle("tier_title"),
+    Type("type"),
+});
+
+field_enum!(PostField("post") {
+    AppId("app_id"),
+    AppStatus("app_status"),
+    Content("content"),
+    EmbedData("embed_data"),
+    EmbedUrl("embed_url"),
+    IsPaid("is_paid"),
+    IsPublic("is_public"),
+    Tiers("tiers"),
+    PublishedAt("published_at"),
+    Title("title"),
+    Url("url"),
+});
+
+field_enum!(WebhookField("webhook") {
+    LastAttemptedAt("last_attempted_at"),
+    NumConsecutiveTimesFailed("num_consecutive_times_failed"),
+    Paused("paused"),
+    Secret("secret"),
+    Triggers("triggers"),
+    Uri("uri"),
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_to_writes_include_and_fields() {
+        let query = Query::new()
+            .include("memberships")
+            .include("memberships.campaign")
+            .include("memberships")
+            .fields(&[
+                MemberField::FullName,
+                MemberField::Email,
+                MemberField::FullName,
+            ])
+            .fields(&[CampaignField::Vanity]);
+        let mut url = Url::parse("https://www.patreon.com/api/oauth2/v2/identity").unwrap();
+        query.append_to(&mut url);
+        let pairs = url.query_pairs().into_owned().collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            [
+                (
+                    "include".to_string(),
+                    "memberships,memberships.campaign".to_string()
+                ),
+                ("fields[campaign]".to_string(), "vanity".to_string()),
+                ("fields[member]".to_string(), "full_name,email".to_string()),
+            ]
+        );
+
+        let mut url = Url::parse("https://www.patreon.com/api/oauth2/v2/identity").unwrap();
+        Query::new().append_to(&mut url);
+        assert_eq!(url.query(), None);
+    }
+
+    #[test]
+    fn has_include_matches_whole_path_segments() {
+        let query = Query::new()
+            .include("memberships.campaign")
+            .include("addresses");
+        assert!(query.has_include("memberships"));
+        assert!(query.has_include("memberships.campaign"));
+        assert!(!query.has_include("campaign"));
+        assert!(!query.has_include("address"));
+        assert!(!query.has_include("memberships.campaign.creator"));
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Token bucket shared through `Arc` by any number of `PatreonApi` and `PatreonOAuth` clients.
+/// Requests wait in line (first come, first served) for a token instead of failing.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    refill_every: Duration,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Allows bursts of `requests` and on average `requests` per `per`.
+    pub fn new(requests: u32, per: Duration) -> Self {
+        let requests = requests.max(1);
+        Self {
+            capacity: requests as f64,
+            refill_every: per / requests,
+            bucket: Mutex::new(Bucket {
+                tokens: requests as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a token is available and takes it.
+    pub async fn acquire(&self) {
+        // the lock is held while sleeping, so waiting requests are served in order
+        let mut bucket = self.bucket.lock().await;
+        loop {
+            let now = Instant::now();
+            let refilled = now.duration_since(bucket.refilled_at).as_secs_f64()
+                / self.refill_every.as_secs_f64().max(f64::MIN_POSITIVE);
+            bucket.tokens = (bucket.tokens + refilled).min(self.capacity);
+            bucket.refilled_at = now;
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return;
+            }
+            tokio::time::sleep(self.refill_every.mul_f64(1.0 - bucket.tokens)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_then_one_request_per_interval() {
+        let limiter = Arc::new(RateLimiter::new(3, Duration::from_secs(3)));
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        let served = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut waiters = Vec::new();
+        for index in 0..3 {
+            let limiter = limiter.clone();
+            let served = served.clone();
+            waiters.push(tokio::spawn(async move {
+                limiter.acquire().await;
+                served.lock().unwrap().push((index, start.elapsed()));
+            }));
+            // let the waiter queue on the lock before the next one is spawned
+            tokio::task::yield_now().await;
+        }
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+        let served = served.lock().unwrap().clone();
+        assert_eq!(served.len(), 3);
+        for (position, (index, elapsed)) in served.into_iter().enumerate() {
+            assert_eq!(index, position);
+            let expected = Duration::from_secs(position as u64 + 1);
+            assert!(
+                elapsed.abs_diff(expected) < Duration::from_millis(10),
+                "{elapsed:?}"
+            );
+        }
+    }
+}
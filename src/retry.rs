@@ -0,0 +1,98 @@
+use crate::PatreonError;
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Decides whether a failed request is sent again, only idempotent requests
+/// (GETs and token refreshes) are ever offered to a policy.
+pub trait RetryPolicy: Debug + Send + Sync {
+    /// Delay before retry number `attempt` (starting at 1), `None` gives up and returns `error`.
+    fn retry_after(&self, attempt: u32, error: &PatreonError) -> Option<Duration>;
+}
+
+/// Never retries.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoRetry;
+
+impl RetryPolicy for NoRetry {
+    fn retry_after(&self, _attempt: u32, _error: &PatreonError) -> Option<Duration> {
+        None
+    }
+}
+
+/// Retries transient errors (see `PatreonError::is_transient`), doubling the delay every attempt.
+/// Half of each delay is random so clients failing together do not retry together.
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialBackoff {
+    pub max_retries: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn retry_after(&self, attempt: u32, error: &PatreonError) -> Option<Duration> {
+        if attempt > self.max_retries || !error.is_transient() {
+            return None;
+        }
+        let delay = self
+            .initial_delay
+            .checked_mul(1 << (attempt - 1).min(31))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        Some(delay / 2 + jitter(delay / 2))
+    }
+}
+
+fn jitter(max: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64(random as f64 / u64::MAX as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScopeSet;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn backoff_doubles_up_to_max_delay() {
+        let policy = ExponentialBackoff {
+            max_retries: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+        };
+        let unavailable = PatreonError::PatreonApi(StatusCode::SERVICE_UNAVAILABLE, vec![]);
+        for (attempt, delay) in [(1, 1), (2, 2), (3, 4), (4, 5), (5, 5)] {
+            let delay = Duration::from_secs(delay);
+            for _ in 0..20 {
+                let wait = policy.retry_after(attempt, &unavailable).unwrap();
+                assert!(wait >= delay / 2 && wait <= delay, "{attempt}: {wait:?}");
+            }
+        }
+        assert_eq!(policy.retry_after(6, &unavailable), None);
+    }
+
+    #[test]
+    fn backoff_skips_permanent_errors() {
+        let policy = ExponentialBackoff::default();
+        let bad_request = PatreonError::PatreonApi(StatusCode::BAD_REQUEST, vec![]);
+        assert_eq!(policy.retry_after(1, &bad_request), None);
+        let missing_scope = PatreonError::MissingScope {
+            required: ScopeSet::new(),
+            granted: None,
+            errors: vec![],
+        };
+        assert_eq!(policy.retry_after(1, &missing_scope), None);
+    }
+}
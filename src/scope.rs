@@ -0,0 +1,146 @@
+use crate::api::enum_str;
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+enum_str!(Scope {
+    Identity("identity"),
+    IdentityEmail("identity[email]"),
+    IdentityMemberships("identity.memberships"),
+    Campaigns("campaigns"),
+    CampaignsMembers("campaigns.members"),
+    CampaignsMembersEmail("campaigns.members[email]"),
+    CampaignsMembersAddress("campaigns.members.address"),
+    CampaignsPosts("campaigns.posts"),
+    CampaignsWebhook("w:campaigns.webhook"),
+    // v1
+    Users("users"),
+    PledgesToMe("pledges-to-me"),
+    MyCampaign("my-campaign"),
+});
+
+impl Scope {
+    /// v1 scopes are not listed in the `scope` of v2 tokens, even when the v1 api accepts the token.
+    pub(crate) fn is_v1(&self) -> bool {
+        matches!(self, Scope::Users | Scope::PledgesToMe | Scope::MyCampaign)
+    }
+}
+
+/// Scopes requested from or granted by Patreon, written space separated as in OAuth.
+#[derive(Default, Debug, Clone)]
+pub struct ScopeSet {
+    scopes: Vec<Scope>,
+}
+
+impl ScopeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a scope, duplicates are ignored.
+    pub fn with(mut self, scope: Scope) -> Self {
+        self.insert(scope);
+        self
+    }
+
+    pub fn insert(&mut self, scope: Scope) {
+        if !self.scopes.contains(&scope) {
+            self.scopes.push(scope);
+        }
+    }
+
+    pub fn contains(&self, scope: &Scope) -> bool {
+        self.scopes.contains(scope)
+    }
+
+    /// Whether every scope of `other` is in this set.
+    pub fn contains_all(&self, other: &ScopeSet) -> bool {
+        other.iter().all(|scope| self.contains(scope))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Scope> {
+        self.scopes.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+}
+
+/// Sets are equal when they hold the same scopes, in any order.
+impl PartialEq for ScopeSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.scopes.len() == other.scopes.len() && self.contains_all(other)
+    }
+}
+
+impl Eq for ScopeSet {}
+
+impl FromIterator<Scope> for ScopeSet {
+    fn from_iter<T: IntoIterator<Item = Scope>>(iter: T) -> Self {
+        let mut set = ScopeSet::new();
+        for scope in iter {
+            set.insert(scope);
+        }
+        set
+    }
+}
+
+impl From<&[Scope]> for ScopeSet {
+    fn from(scopes: &[Scope]) -> Self {
+        scopes.iter().cloned().collect()
+    }
+}
+
+impl<const N: usize> From<[Scope; N]> for ScopeSet {
+    fn from(scopes: [Scope; N]) -> Self {
+        scopes.into_iter().collect()
+    }
+}
+
+impl FromStr for ScopeSet {
+    type Err = Infallible;
+
+    fn from_str(scopes: &str) -> Result<Self, Self::Err> {
+        Ok(scopes.split_whitespace().map(Scope::from).collect())
+    }
+}
+
+impl Display for ScopeSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, scope) in self.scopes.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(scope.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl serde::Serialize for ScopeSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ScopeSet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let scopes = <String as serde::Deserialize>::deserialize(deserializer)?;
+        Ok(scopes.parse().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equality_ignores_order() {
+        let a = ScopeSet::from([Scope::Identity, Scope::Campaigns]);
+        let b = ScopeSet::from([Scope::Campaigns, Scope::Identity, Scope::Campaigns]);
+        assert_eq!(a, b);
+        assert_ne!(a, ScopeSet::from([Scope::Identity]));
+        assert_eq!("campaigns identity".parse::<ScopeSet>().unwrap(), a);
+    }
+}
@@ -0,0 +1,455 @@
+use crate::{PatreonError, PatreonResult, Tokens};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use futures::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// Persistence of `Tokens`, keyed by the id of the user or creator the tokens belong to.
+pub trait TokenStore: Debug + Send + Sync {
+    fn load<'a>(&'a self, key: &'a str) -> BoxFuture<'a, PatreonResult<Option<Tokens>>>;
+
+    fn save<'a>(&'a self, key: &'a str, tokens: &'a Tokens) -> BoxFuture<'a, PatreonResult<()>>;
+
+    fn remove<'a>(&'a self, key: &'a str) -> BoxFuture<'a, PatreonResult<()>>;
+}
+
+/// Keeps tokens for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<HashMap<String, Tokens>>,
+}
+
+impl MemoryTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load<'a>(&'a self, key: &'a str) -> BoxFuture<'a, PatreonResult<Option<Tokens>>> {
+        Box::pin(async move { Ok(self.tokens.lock().await.get(key).cloned()) })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, tokens: &'a Tokens) -> BoxFuture<'a, PatreonResult<()>> {
+        Box::pin(async move {
+            self.tokens
+                .lock()
+                .await
+                .insert(key.to_string(), tokens.clone());
+            Ok(())
+        })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> BoxFuture<'a, PatreonResult<()>> {
+        Box::pin(async move {
+            self.tokens.lock().await.remove(key);
+            Ok(())
+        })
+    }
+}
+
+/// Keeps every key in one JSON object on disk, a save never leaves a half written file behind.
+#[derive(Debug)]
+pub struct FileTokenStore {
+    file: JsonFile,
+}
+
+impl FileTokenStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            file: JsonFile::new(path.into()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path.as_path()
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load<'a>(&'a self, key: &'a str) -> BoxFuture<'a, PatreonResult<Option<Tokens>>> {
+        Box::pin(async move {
+            let _lock = self.file.lock.lock().await;
+            Ok(self.file.read::<Tokens>().await?.remove(key))
+        })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, tokens: &'a Tokens) -> BoxFuture<'a, PatreonResult<()>> {
+        Box::pin(async move {
+            let _lock = self.file.lock.lock().await;
+            let mut all = self.file.read().await?;
+            all.insert(key.to_string(), tokens.clone());
+            self.file.write(&all).await
+        })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> BoxFuture<'a, PatreonResult<()>> {
+        Box::pin(async move {
+            let _lock = self.file.lock.lock().await;
+            let mut all = self.file.read::<Tokens>().await?;
+            if all.remove(key).is_some() {
+                self.file.write(&all).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// A 256 bit key of `EncryptedFileTokenStore`, the id is stored with every record it encrypts.
+#[derive(Clone)]
+pub struct TokenKey {
+    id: String,
+    key: Key,
+}
+
+impl TokenKey {
+    pub fn new(id: impl Into<String>, key: [u8; 32]) -> Self {
+        Self {
+            id: id.into(),
+            key: key.into(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(&self.key)
+    }
+}
+
+impl Debug for TokenKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Like `FileTokenStore`, but every record is sealed with ChaCha20-Poly1305 under `key`.
+/// Records sealed with one of the `old_keys` are re-sealed with `key` when loaded.
+/// A record that cannot be opened fails with `PatreonError::TokenDecryption`, it is never skipped.
+#[derive(Debug)]
+pub struct EncryptedFileTokenStore {
+    file: JsonFile,
+    key: TokenKey,
+    old_keys: Vec<TokenKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SealedTokens {
+    key_id: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl EncryptedFileTokenStore {
+    pub fn new(path: impl Into<PathBuf>, key: TokenKey) -> Self {
+        Self {
+            file: JsonFile::new(path.into()),
+            key,
+            old_keys: vec![],
+        }
+    }
+
+    /// Keys records may still be sealed with, tried by id.
+    pub fn old_keys(mut self, old_keys: Vec<TokenKey>) -> Self {
+        self.old_keys = old_keys;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path.as_path()
+    }
+
+    // the record key is the associated data, so records cannot be swapped between users
+    fn seal(&self, key: &str, tokens: &Tokens) -> PatreonResult<SealedTokens> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(tokens)?;
+        let ciphertext = self
+            .key
+            .cipher()
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext.as_slice(),
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| PatreonError::Message(format!("failed to encrypt tokens of {key}")))?;
+        Ok(SealedTokens {
+            key_id: self.key.id.clone(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    fn open(&self, key: &str, sealed: &SealedTokens) -> PatreonResult<Tokens> {
+        let failed = |reason: &str| {
+            PatreonError::TokenDecryption(format!(
+                "tokens of {key} (key {}) : {reason}",
+                sealed.key_id
+            ))
+        };
+        let token_key = std::iter::once(&self.key)
+            .chain(&self.old_keys)
+            .find(|token_key| token_key.id == sealed.key_id)
+            .ok_or_else(|| failed("unknown key"))?;
+        let nonce = hex::decode(&sealed.nonce).map_err(|_| failed("malformed nonce"))?;
+        if nonce.len() != 12 {
+            return Err(failed("malformed nonce"));
+        }
+        let ciphertext =
+            hex::decode(&sealed.ciphertext).map_err(|_| failed("malformed ciphertext"))?;
+        let plaintext = token_key
+            .cipher()
+            .decrypt(
+                Nonce::from_slice(nonce.as_slice()),
+                Payload {
+                    msg: ciphertext.as_slice(),
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| failed("authentication failed"))?;
+        serde_json::from_slice(plaintext.as_slice()).map_err(|_| failed("malformed tokens"))
+    }
+}
+
+impl TokenStore for EncryptedFileTokenStore {
+    fn load<'a>(&'a self, key: &'a str) -> BoxFuture<'a, PatreonResult<Option<Tokens>>> {
+        Box::pin(async move {
+            let _lock = self.file.lock.lock().await;
+            let mut all = self.file.read::<SealedTokens>().await?;
+            let Some(sealed) = all.get(key) else {
+                return Ok(None);
+            };
+            let tokens = self.open(key, sealed)?;
+            if sealed.key_id != self.key.id {
+                all.insert(key.to_string(), self.seal(key, &tokens)?);
+                self.file.write(&all).await?;
+            }
+            Ok(Some(tokens))
+        })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, tokens: &'a Tokens) -> BoxFuture<'a, PatreonResult<()>> {
+        Box::pin(async move {
+            let _lock = self.file.lock.lock().await;
+            let mut all = self.file.read().await?;
+            all.insert(key.to_string(), self.seal(key, tokens)?);
+            self.file.write(&all).await
+        })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> BoxFuture<'a, PatreonResult<()>> {
+        Box::pin(async move {
+            let _lock = self.file.lock.lock().await;
+            let mut all = self.file.read::<SealedTokens>().await?;
+            if all.remove(key).is_some() {
+                self.file.write(&all).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// A JSON object on disk, `lock` serializes read-modify-write cycles of this instance only,
+/// two instances on the same path can lose each other's updates.
+#[derive(Debug)]
+struct JsonFile {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl JsonFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    async fn read<T: DeserializeOwned>(&self) -> PatreonResult<HashMap<String, T>> {
+        match tokio::fs::read(&self.path).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(err) => Err(io_error(&self.path, err)),
+        }
+    }
+
+    /// Writes a private (0600) file next to the store, syncs it and renames it over the store.
+    async fn write<T: Serialize>(&self, records: &HashMap<String, T>) -> PatreonResult<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp = PathBuf::from(tmp);
+        let bytes = serde_json::to_vec_pretty(records)?;
+        let written = async {
+            let mut options = tokio::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+            let mut file = options.open(&tmp).await?;
+            file.write_all(&bytes).await?;
+            file.sync_all().await
+        }
+        .await;
+        if let Err(err) = written {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(io_error(&tmp, err));
+        }
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .map_err(|err| io_error(&self.path, err))?;
+        // the rename itself is only durable once the directory is synced
+        #[cfg(unix)]
+        if let Some(dir) = self.path.parent() {
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            let synced = match tokio::fs::File::open(dir).await {
+                Ok(dir) => dir.sync_all().await,
+                Err(err) => Err(err),
+            };
+            synced.map_err(|err| io_error(dir, err))?;
+        }
+        Ok(())
+    }
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> PatreonError {
+    PatreonError::Message(format!("{} : {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "patreon-token-store-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("tokens.json")
+    }
+
+    fn tokens(refresh_token: &str) -> Tokens {
+        Tokens {
+            access_token: "access".to_string(),
+            refresh_token: refresh_token.to_string(),
+            expires_at: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn file_store_round_trip() {
+        let path = temp_path("file");
+        let store = FileTokenStore::new(&path);
+        assert_eq!(store.load("a").await.unwrap(), None);
+        store.save("a", &tokens("ra")).await.unwrap();
+        store.save("b", &tokens("rb")).await.unwrap();
+        store.remove("a").await.unwrap();
+        assert_eq!(store.load("a").await.unwrap(), None);
+        assert_eq!(store.load("b").await.unwrap().unwrap().refresh_token, "rb");
+        // only the store itself is left, no temp files
+        let files = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(files, 1);
+    }
+
+    fn sealed_records(path: &Path) -> serde_json::Value {
+        serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn seal_open_round_trip() {
+        let store = EncryptedFileTokenStore::new(temp_path("seal"), TokenKey::new("k1", [1; 32]));
+        let sealed = store.seal("a", &tokens("secret")).unwrap();
+        assert_eq!(sealed.key_id, "k1");
+        assert_eq!(store.open("a", &sealed).unwrap().refresh_token, "secret");
+    }
+
+    #[test]
+    fn tampered_or_moved_records_fail() {
+        let store = EncryptedFileTokenStore::new(temp_path("tamper"), TokenKey::new("k1", [1; 32]));
+        let sealed = store.seal("a", &tokens("secret")).unwrap();
+        let mut ciphertext = hex::decode(&sealed.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        let tampered = SealedTokens {
+            ciphertext: hex::encode(ciphertext),
+            ..sealed
+        };
+        assert!(matches!(
+            store.open("a", &tampered),
+            Err(PatreonError::TokenDecryption(_))
+        ));
+        let sealed = store.seal("a", &tokens("secret")).unwrap();
+        assert!(matches!(
+            store.open("b", &sealed),
+            Err(PatreonError::TokenDecryption(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unknown_key_fails() {
+        let path = temp_path("unknown");
+        EncryptedFileTokenStore::new(&path, TokenKey::new("k1", [1; 32]))
+            .save("a", &tokens("secret"))
+            .await
+            .unwrap();
+        let result = EncryptedFileTokenStore::new(&path, TokenKey::new("k2", [2; 32]))
+            .load("a")
+            .await;
+        assert!(matches!(result, Err(PatreonError::TokenDecryption(_))));
+    }
+
+    #[tokio::test]
+    async fn old_key_records_are_resealed() {
+        let path = temp_path("rotate");
+        let old = TokenKey::new("k1", [1; 32]);
+        EncryptedFileTokenStore::new(&path, old.clone())
+            .save("a", &tokens("secret"))
+            .await
+            .unwrap();
+        let store =
+            EncryptedFileTokenStore::new(&path, TokenKey::new("k2", [2; 32])).old_keys(vec![old]);
+        let loaded = store.load("a").await.unwrap().unwrap();
+        assert_eq!(loaded.refresh_token, "secret");
+        assert_eq!(sealed_records(&path)["a"]["key_id"], "k2");
+        let rotated = EncryptedFileTokenStore::new(&path, TokenKey::new("k2", [2; 32]));
+        assert_eq!(rotated.load("a").await.unwrap(), Some(loaded));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn file_store_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_path("mode");
+        FileTokenStore::new(&path)
+            .save("a", &tokens("ra"))
+            .await
+            .unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}